@@ -63,13 +63,54 @@ export interface PsiResult {
   demoMode?: boolean;
 }
 
+/** A registration an attestation service is asked to vouch for. */
+export interface AttestationRequest {
+  wallet: anchor.web3.PublicKey;
+  /** Identifier the registrant claims, e.g. a phone number */
+  identifier: string;
+}
+
+/**
+ * The attestation service's own encryption of the verified identifier's
+ * hash to the MXE. The program only inserts the registrant's entry when
+ * it decrypts to the same hash.
+ */
+export interface AttestedHash {
+  /** Rescue ciphertext of the identifier hash */
+  encryptedHash: Uint8Array;
+  /** Service's ephemeral x25519 public key */
+  pubKey: Uint8Array;
+  nonce: Uint8Array;
+}
+
+/**
+ * Ed25519 signature by the registry's attestation key over the program's
+ * `attestation_message` for `attestedHash`, after the service verified the
+ * identifier (e.g. by SMS or email OTP).
+ */
+export interface Attestation {
+  attestationKey: anchor.web3.PublicKey;
+  attestedHash: AttestedHash;
+  message: Uint8Array;
+  signature: Uint8Array;
+  /** Unix timestamp the signature is valid until */
+  expiry: number;
+}
+
 // ── Constants ───────────────────────────────────────────────────────────
 
 const MAX_CLIENT_CONTACTS = 16;
 const ARCIUM_CLUSTER_OFFSET = 456;
 const REGISTRY_SEED = Buffer.from("blind_link_registry");
+const REGISTRY_SHARD_SEED = Buffer.from("registry_shard");
+const NUM_REGISTRY_SHARDS = 4;
 const SESSION_SEED = Buffer.from("psi_session");
 const SIGN_PDA_SEED = Buffer.from("ArciumSignerAccount");
+const ESCROW_SEED = Buffer.from("registration_escrow");
+const REGISTRATION_SEED = Buffer.from("registration");
+const MEMBERSHIP_SEED = Buffer.from("membership_session");
+/** localStorage key prefix for each wallet's ownership secret */
+const OWNER_SECRET_KEY_PREFIX = "blind-link:owner-secret:";
 
 // ── Client Service ──────────────────────────────────────────────────────
 
@@ -232,14 +273,15 @@ export class BlindLinkClient {
       this.program.programId
     );
 
-    // Submit the PSI computation
-    const txSignature = await this.program.methods
+    // Submit the PSI computation. It bumps the matched entries' hit
+    // counters, so it takes the registry write lock; resubmit while locked.
+    const txSignature = await retryWhileRegistryLocked(() => this.program.methods
       .intersectContacts(
         computationOffset,
         encryptedHashes.map((h: Uint8Array) => Array.from(h)),
         Array.from(encryptedCount),
-        Array.from(this.clientPublicKey),
-        new anchor.BN(deserializeLE(this.sessionNonce).toString())
+        Array.from(this.clientPublicKey!),
+        new anchor.BN(deserializeLE(this.sessionNonce!).toString())
       )
       .accountsPartial({
         user: this.provider.wallet.publicKey,
@@ -269,7 +311,8 @@ export class BlindLinkClient {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: getArciumProgramId(),
       })
-      .rpc({ commitment: "confirmed" });
+      .remainingAccounts(registryShardAccounts(this.program.programId))
+      .rpc({ commitment: "confirmed" }));
 
     return { computationOffset, txSignature };
   }
@@ -312,8 +355,10 @@ export class BlindLinkClient {
     // Account type resolved from IDL after `anchor build` generates types
     const session = await (this.program.account as any).psiSession.fetch(sessionPda);
 
-    if (session.status !== 2) {
-      throw new Error(`PSI computation failed with status: ${session.status}`);
+    // SessionStatus is an Anchor enum, decoded as e.g. `{ completed: {} }`
+    if (!("completed" in session.status)) {
+      const status = Object.keys(session.status)[0];
+      throw new Error(`PSI computation failed with status: ${status}`);
     }
 
     // Decrypt the match results
@@ -456,29 +501,36 @@ export class BlindLinkClient {
 
   /**
    * Register the current user's contact hash in the Global Registry.
-   * Should be called once during initial app onboarding.
+   * Should be called once during initial app onboarding. Opens the wallet's
+   * registration escrow with the configured lamport stake first if needed.
+   * Pass `attest` when the registry requires attested registrations.
    */
-  async registerSelf(contactIdentifier: string): Promise<string> {
+  async registerSelf(
+    contactIdentifier: string,
+    attest?: (request: AttestationRequest) => Promise<Attestation>
+  ): Promise<string> {
     await this.initSession();
 
-    if (!this.cipher || !this.clientPublicKey || !this.sessionNonce) {
-      throw new Error("Session initialization failed");
+    const escrowPda = registrationEscrowPda(
+      this.program.programId,
+      this.provider.wallet.publicKey
+    );
+    if (!(await this.provider.connection.getAccountInfo(escrowPda))) {
+      await this.program.methods
+        .depositStake()
+        .accountsPartial({
+          user: this.provider.wallet.publicKey,
+          registrationEscrow: escrowPda,
+        })
+        .rpc({ commitment: "confirmed" });
     }
 
-    // Hash the user's own contact identifier
-    const encoder = new TextEncoder();
-    const data = encoder.encode(contactIdentifier.trim().toLowerCase());
-    const hashBuffer = await crypto.subtle.digest("SHA-256", data);
-    const hashArray = new Uint8Array(hashBuffer);
-
-    let selfHash = BigInt(0);
-    for (let i = 0; i < 16; i++) {
-      selfHash |= BigInt(hashArray[i]) << BigInt(i * 8);
+    if (!this.cipher || !this.clientPublicKey || !this.sessionNonce) {
+      throw new Error("Session initialization failed");
     }
 
-    // Encrypt the self-hash
-    const ciphertexts = this.cipher.encrypt([selfHash], this.sessionNonce);
-    const encryptedHash = Uint8Array.from(ciphertexts[0]);
+    const { encryptedHash, encryptedOwnerSecret } =
+      await this.encryptRegistrationEntry(contactIdentifier);
 
     const computationOffset = new anchor.BN(randomBytes(8), "hex");
     const [registryPda] = anchor.web3.PublicKey.findProgramAddressSync(
@@ -491,16 +543,40 @@ export class BlindLinkClient {
       this.program.programId
     );
 
-    const txSignature = await this.program.methods
+    // The Ed25519 instruction must directly precede register_user
+    const attestation = await attest?.({
+      wallet: this.provider.wallet.publicKey,
+      identifier: contactIdentifier,
+    });
+    const preInstructions = attestation
+      ? [
+          anchor.web3.Ed25519Program.createInstructionWithPublicKey({
+            publicKey: attestation.attestationKey.toBytes(),
+            message: attestation.message,
+            signature: attestation.signature,
+          }),
+        ]
+      : [];
+
+    // Only one registry write may be in flight; resubmit while locked
+    const txSignature = await retryWhileRegistryLocked(() => this.program.methods
       .registerUser(
         computationOffset,
         Array.from(encryptedHash),
+        Array.from(encryptedOwnerSecret),
         Array.from(this.clientPublicKey),
-        new anchor.BN(deserializeLE(this.sessionNonce).toString())
+        new anchor.BN(deserializeLE(this.sessionNonce).toString()),
+        attestedHashArg(attestation),
+        new anchor.BN(attestation?.expiry ?? 0)
       )
       .accountsPartial({
         user: this.provider.wallet.publicKey,
         registryState: registryPda,
+        registrationEscrow: escrowPda,
+        registration: registrationPda(
+          this.program.programId,
+          this.provider.wallet.publicKey
+        ),
         signPdaAccount: signPda,
         mxeAccount: getMXEAccAddress(this.program.programId),
         mempoolAccount: getMempoolAccAddress(
@@ -525,7 +601,9 @@ export class BlindLinkClient {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: getArciumProgramId(),
       })
-      .rpc({ commitment: "confirmed" });
+      .remainingAccounts(registryShardAccounts(this.program.programId))
+      .preInstructions(preInstructions)
+      .rpc({ commitment: "confirmed" }));
 
     await awaitComputationFinalization(
       this.provider,
@@ -537,8 +615,402 @@ export class BlindLinkClient {
     return txSignature;
   }
 
+  /**
+   * Replace the current user's registered identifier (e.g. after a phone
+   * number change) in one MPC computation. Resolves to false, with the old
+   * registration kept, when the registry rejected the update because the
+   * new identifier's bucket is full (or the update failed).
+   */
+  async updateSelf(
+    newIdentifier: string,
+    attest?: (request: AttestationRequest) => Promise<Attestation>
+  ): Promise<boolean> {
+    await this.initSession();
+    if (!this.clientPublicKey || !this.sessionNonce) {
+      throw new Error("Session initialization failed");
+    }
+
+    const user = this.provider.wallet.publicKey;
+    const registration = registrationPda(this.program.programId, user);
+    const before = await (this.program.account as any).registration.fetch(
+      registration
+    );
+    const { encryptedHash, encryptedOwnerSecret } =
+      await this.encryptRegistrationEntry(newIdentifier);
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+
+    const attestation = await attest?.({
+      wallet: user,
+      identifier: newIdentifier,
+    });
+    const preInstructions = attestation
+      ? [
+          anchor.web3.Ed25519Program.createInstructionWithPublicKey({
+            publicKey: attestation.attestationKey.toBytes(),
+            message: attestation.message,
+            signature: attestation.signature,
+          }),
+        ]
+      : [];
+
+    await retryWhileRegistryLocked(() => this.program.methods
+      .updateRegistration(
+        computationOffset,
+        Array.from(encryptedHash),
+        Array.from(encryptedOwnerSecret),
+        Array.from(this.clientPublicKey!),
+        new anchor.BN(deserializeLE(this.sessionNonce!).toString()),
+        attestedHashArg(attestation),
+        new anchor.BN(attestation?.expiry ?? 0)
+      )
+      .accountsPartial({
+        user,
+        registryState: anchor.web3.PublicKey.findProgramAddressSync(
+          [REGISTRY_SEED],
+          this.program.programId
+        )[0],
+        registration,
+        signPdaAccount: anchor.web3.PublicKey.findProgramAddressSync(
+          [SIGN_PDA_SEED],
+          this.program.programId
+        )[0],
+        mxeAccount: getMXEAccAddress(this.program.programId),
+        mempoolAccount: getMempoolAccAddress(this.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(this.arciumClusterOffset),
+        computationAccount: getComputationAccAddress(
+          this.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          this.program.programId,
+          Buffer.from(getCompDefAccOffset("update_registration")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(this.arciumClusterOffset),
+        poolAccount: getFeePoolAccAddress(),
+        clockAccount: getClockAccAddress(),
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: getArciumProgramId(),
+      })
+      .remainingAccounts(registryShardAccounts(this.program.programId))
+      .preInstructions(preInstructions)
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      this.provider,
+      computationOffset,
+      this.program.programId,
+      "confirmed"
+    );
+
+    // The callback stores a fresh receipt only when the update was applied
+    const after = await (this.program.account as any).registration.fetch(
+      registration
+    );
+    return !after.receiptNonce.eq(before.receiptNonce);
+  }
+
+  /**
+   * Privately check that the current user's registration landed in the
+   * Global Registry. Resolves to true only when `contactIdentifier` is the
+   * identifier this wallet registered and its hash is present. Only this
+   * client can decrypt the answer, so nothing is revealed on-chain.
+   */
+  async checkMembership(contactIdentifier: string): Promise<boolean> {
+    await this.initSession();
+    if (!this.cipher || !this.clientPublicKey || !this.sessionNonce) {
+      throw new Error("Session initialization failed");
+    }
+
+    const user = this.provider.wallet.publicKey;
+    const encryptedHash = await this.encryptIdentifier(contactIdentifier);
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const [membershipPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [MEMBERSHIP_SEED, user.toBuffer(), computationOffset.toArrayLike(Buffer, "le", 8)],
+      this.program.programId
+    );
+
+    await this.program.methods
+      .checkMembership(
+        computationOffset,
+        Array.from(encryptedHash),
+        Array.from(this.clientPublicKey),
+        new anchor.BN(deserializeLE(this.sessionNonce).toString())
+      )
+      .accountsPartial({
+        user,
+        membershipSession: membershipPda,
+        registryState: anchor.web3.PublicKey.findProgramAddressSync(
+          [REGISTRY_SEED],
+          this.program.programId
+        )[0],
+        registration: registrationPda(this.program.programId, user),
+        signPdaAccount: anchor.web3.PublicKey.findProgramAddressSync(
+          [SIGN_PDA_SEED],
+          this.program.programId
+        )[0],
+        mxeAccount: getMXEAccAddress(this.program.programId),
+        mempoolAccount: getMempoolAccAddress(this.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(this.arciumClusterOffset),
+        computationAccount: getComputationAccAddress(
+          this.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          this.program.programId,
+          Buffer.from(getCompDefAccOffset("check_membership")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(this.arciumClusterOffset),
+        poolAccount: getFeePoolAccAddress(),
+        clockAccount: getClockAccAddress(),
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: getArciumProgramId(),
+      })
+      .remainingAccounts(registryShardAccounts(this.program.programId))
+      .rpc({ commitment: "confirmed" });
+
+    await awaitComputationFinalization(
+      this.provider,
+      computationOffset,
+      this.program.programId,
+      "confirmed"
+    );
+
+    const session = await (this.program.account as any).membershipSession.fetch(
+      membershipPda
+    );
+    if (!session.status.completed) {
+      throw new Error("Membership check failed");
+    }
+    const [isMember] = this.cipher.decrypt(
+      [session.resultCiphertext],
+      Uint8Array.from(session.resultNonce)
+    );
+
+    await this.program.methods
+      .closeMembershipSession()
+      .accountsPartial({ user, membershipSession: membershipPda })
+      .rpc({ commitment: "confirmed" });
+
+    return isMember !== BigInt(0);
+  }
+
+  /**
+   * How many times other users' intersections have matched the current
+   * user's registered identifier. Ownership is proven with the ownership
+   * secret this browser registered with; only this client can decrypt the
+   * count. Reads as zero when the identifier or secret does not match.
+   */
+  async readMyHitCount(contactIdentifier: string): Promise<number> {
+    await this.initSession();
+    if (!this.cipher || !this.clientPublicKey || !this.sessionNonce) {
+      throw new Error("Session initialization failed");
+    }
+
+    const user = this.provider.wallet.publicKey;
+    const { encryptedHash, encryptedOwnerSecret } =
+      await this.encryptRegistrationEntry(contactIdentifier);
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+
+    // Set up event listener BEFORE submitting (avoid race condition)
+    const resultPromise = new Promise<any>((resolve, reject) => {
+      const listeners: number[] = [];
+      const done = () =>
+        listeners.forEach((l) => this.program.removeEventListener(l));
+      listeners.push(
+        this.program.addEventListener("hitCountEvent", (event: any) => {
+          if (!event.user.equals(user)) return;
+          done();
+          resolve(event);
+        }) as number,
+        this.program.addEventListener("hitCountFailedEvent", (event: any) => {
+          if (!event.user.equals(user)) return;
+          done();
+          reject(new Error(`Hit count read failed (reason ${event.reason})`));
+        }) as number
+      );
+    });
+
+    await this.program.methods
+      .readMyHitCount(
+        computationOffset,
+        Array.from(encryptedHash),
+        Array.from(encryptedOwnerSecret),
+        Array.from(this.clientPublicKey),
+        new anchor.BN(deserializeLE(this.sessionNonce).toString())
+      )
+      .accountsPartial({
+        user,
+        registration: registrationPda(this.program.programId, user),
+        registryState: anchor.web3.PublicKey.findProgramAddressSync(
+          [REGISTRY_SEED],
+          this.program.programId
+        )[0],
+        signPdaAccount: anchor.web3.PublicKey.findProgramAddressSync(
+          [SIGN_PDA_SEED],
+          this.program.programId
+        )[0],
+        mxeAccount: getMXEAccAddress(this.program.programId),
+        mempoolAccount: getMempoolAccAddress(this.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(this.arciumClusterOffset),
+        computationAccount: getComputationAccAddress(
+          this.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          this.program.programId,
+          Buffer.from(getCompDefAccOffset("read_my_hit_count")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(this.arciumClusterOffset),
+        poolAccount: getFeePoolAccAddress(),
+        clockAccount: getClockAccAddress(),
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: getArciumProgramId(),
+      })
+      .remainingAccounts(registryShardAccounts(this.program.programId))
+      .rpc({ commitment: "confirmed" });
+
+    await awaitComputationFinalization(
+      this.provider,
+      computationOffset,
+      this.program.programId,
+      "confirmed"
+    );
+
+    const event = await resultPromise;
+    const [hits] = this.cipher.decrypt(
+      [event.resultCiphertext],
+      Uint8Array.from(event.resultNonce)
+    );
+    return Number(hits);
+  }
+
+  /**
+   * Status of the current user's on-chain Registration ("pending",
+   * "registered" or "deregistering"), or null if the wallet is not
+   * registered.
+   */
+  async getRegistrationStatus(): Promise<string | null> {
+    const registration = await (this.program.account as any).registration.fetchNullable(
+      registrationPda(this.program.programId, this.provider.wallet.publicKey)
+    );
+    return registration ? Object.keys(registration.status)[0] : null;
+  }
+
+  /**
+   * Remove the current user's registration, using the receipt held in
+   * their Registration, then return the lamport stake and close the escrow.
+   */
+  async deregisterSelf(): Promise<string> {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const user = this.provider.wallet.publicKey;
+    const escrowPda = registrationEscrowPda(this.program.programId, user);
+    const registration = registrationPda(this.program.programId, user);
+
+    await retryWhileRegistryLocked(() => this.program.methods
+      .deregisterUser(computationOffset)
+      .accountsPartial({
+        payer: user,
+        registryState: anchor.web3.PublicKey.findProgramAddressSync(
+          [REGISTRY_SEED],
+          this.program.programId
+        )[0],
+        registration,
+        signPdaAccount: anchor.web3.PublicKey.findProgramAddressSync(
+          [SIGN_PDA_SEED],
+          this.program.programId
+        )[0],
+        mxeAccount: getMXEAccAddress(this.program.programId),
+        mempoolAccount: getMempoolAccAddress(this.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(this.arciumClusterOffset),
+        computationAccount: getComputationAccAddress(
+          this.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          this.program.programId,
+          Buffer.from(getCompDefAccOffset("deregister_user")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(this.arciumClusterOffset),
+        poolAccount: getFeePoolAccAddress(),
+        clockAccount: getClockAccAddress(),
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: getArciumProgramId(),
+      })
+      .remainingAccounts(registryShardAccounts(this.program.programId))
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      this.provider,
+      computationOffset,
+      this.program.programId,
+      "confirmed"
+    );
+
+    return this.program.methods
+      .withdrawStake()
+      .accountsPartial({
+        user,
+        registrationEscrow: escrowPda,
+        registration,
+        escrowVault: null,
+        userTokenAccount: null,
+        tokenProgram: null,
+      })
+      .rpc({ commitment: "confirmed" });
+  }
+
   // ── Internal Helpers ────────────────────────────────────────────────
 
+  /**
+   * Hash a contact identifier (salted SHA-256 truncated to 128 bits) and
+   * encrypt it under the session key.
+   */
+  private async encryptIdentifier(contactIdentifier: string): Promise<Uint8Array> {
+    if (!this.cipher || !this.sessionNonce) {
+      throw new Error("Session initialization failed");
+    }
+    const selfHash = await hashIdentifier(contactIdentifier);
+    const ciphertexts = this.cipher.encrypt([selfHash], this.sessionNonce);
+    return Uint8Array.from(ciphertexts[0]);
+  }
+
+  /**
+   * Hash a contact identifier and encrypt it together with the wallet's
+   * ownership secret under the session key and nonce, as register_user,
+   * update_registration and read_my_hit_count expect.
+   */
+  private async encryptRegistrationEntry(
+    contactIdentifier: string
+  ): Promise<{ encryptedHash: Uint8Array; encryptedOwnerSecret: Uint8Array }> {
+    if (!this.cipher || !this.sessionNonce) {
+      throw new Error("Session initialization failed");
+    }
+    const selfHash = await hashIdentifier(contactIdentifier);
+    const ciphertexts = this.cipher.encrypt(
+      [selfHash, this.ownerSecret()],
+      this.sessionNonce
+    );
+    return {
+      encryptedHash: Uint8Array.from(ciphertexts[0]),
+      encryptedOwnerSecret: Uint8Array.from(ciphertexts[1]),
+    };
+  }
+
+  /**
+   * The wallet's 128-bit ownership secret, generated on first use and kept
+   * in localStorage. Losing it only loses access to the hit count until
+   * the next updateSelf, which registers the current secret.
+   */
+  private ownerSecret(): bigint {
+    const key = `${OWNER_SECRET_KEY_PREFIX}${this.provider.wallet.publicKey.toBase58()}`;
+    let stored = localStorage.getItem(key);
+    if (!stored) {
+      stored = randomBytes(16).toString("hex");
+      localStorage.setItem(key, stored);
+    }
+    return deserializeLE(Uint8Array.from(Buffer.from(stored, "hex")));
+  }
+
   /** Event listener for PSI completion (set up before tx submission). */
   private awaitPsiEvent(): Promise<any> {
     return new Promise((resolve) => {
@@ -555,6 +1027,87 @@ export class BlindLinkClient {
 
 // ── Utilities ───────────────────────────────────────────────────────────
 
+/** Salted SHA-256 of a normalized identifier, truncated to 128 bits (LE). */
+async function hashIdentifier(contactIdentifier: string): Promise<bigint> {
+  const encoder = new TextEncoder();
+  const data = encoder.encode(contactIdentifier.trim().toLowerCase());
+  const hashArray = new Uint8Array(await crypto.subtle.digest("SHA-256", data));
+
+  let hash = BigInt(0);
+  for (let i = 0; i < 16; i++) {
+    hash |= BigInt(hashArray[i]) << BigInt(i * 8);
+  }
+  return hash;
+}
+
+/** Registry shard PDAs, in index order, as read-only remaining accounts. */
+function registryShardAccounts(
+  programId: anchor.web3.PublicKey
+): anchor.web3.AccountMeta[] {
+  return Array.from({ length: NUM_REGISTRY_SHARDS }, (_, i) => ({
+    pubkey: anchor.web3.PublicKey.findProgramAddressSync(
+      [REGISTRY_SHARD_SEED, Buffer.from([i])],
+      programId
+    )[0],
+    isWritable: false,
+    isSigner: false,
+  }));
+}
+
+/** The `attested` instruction argument for an optional attestation. */
+function attestedHashArg(attestation?: Attestation) {
+  if (!attestation) return null;
+  const { encryptedHash, pubKey, nonce } = attestation.attestedHash;
+  return {
+    encryptedHash: Array.from(encryptedHash),
+    pubKey: Array.from(pubKey),
+    nonce: new anchor.BN(deserializeLE(nonce).toString()),
+  };
+}
+
+/** A wallet's RegistrationEscrow PDA (registration stake). */
+function registrationEscrowPda(
+  programId: anchor.web3.PublicKey,
+  user: anchor.web3.PublicKey
+): anchor.web3.PublicKey {
+  return anchor.web3.PublicKey.findProgramAddressSync(
+    [ESCROW_SEED, user.toBuffer()],
+    programId
+  )[0];
+}
+
+/** A wallet's Registration PDA (status and registration receipt). */
+function registrationPda(
+  programId: anchor.web3.PublicKey,
+  user: anchor.web3.PublicKey
+): anchor.web3.PublicKey {
+  return anchor.web3.PublicKey.findProgramAddressSync(
+    [REGISTRATION_SEED, user.toBuffer()],
+    programId
+  )[0];
+}
+
+/**
+ * Resubmit a registry-writing transaction while another registration holds
+ * the on-chain write lock (RegistryWriteLocked). The lock is released when
+ * the in-flight computation's callback lands.
+ */
+async function retryWhileRegistryLocked<T>(
+  submit: () => Promise<T>,
+  maxRetries = 10,
+  interval = 3000
+): Promise<T> {
+  for (let i = 0; ; i++) {
+    try {
+      return await submit();
+    } catch (e: any) {
+      const locked = String(e?.message ?? e).includes("RegistryWriteLocked");
+      if (!locked || i >= maxRetries - 1) throw e;
+      await new Promise((r) => setTimeout(r, interval));
+    }
+  }
+}
+
 /** Retry helper for MXE public key retrieval (devnet latency). */
 async function getMXEPublicKeyWithRetry(
   provider: anchor.AnchorProvider,
@@ -13,6 +13,11 @@
 // client contacts. Because bucket indices are secret-shared, MPC cannot
 // branch on them — the arcis compiler converts all if/else into constant-time
 // select operations. All buckets are scanned with a target-bucket guard.
+//
+// Storage: the buckets are split across NUM_SHARDS shard accounts so the
+// registry is not capped by a single account's size; registry-wide counters
+// live in the GlobalRegistry header. Writers return the header and every
+// shard, and the program writes each one back.
 // ============================================================================
 
 use arcis::*;
@@ -24,8 +29,17 @@ mod circuits {
     // ── Registry Constants ──────────────────────────────────────────────
 
     pub const MAX_CLIENT_CONTACTS: usize = 16;
-    pub const NUM_BUCKETS: usize = 4;
+    /// Registry shard accounts; each holds BUCKETS_PER_SHARD buckets.
+    pub const NUM_SHARDS: usize = 4;
+    pub const BUCKETS_PER_SHARD: usize = 4;
+    pub const NUM_BUCKETS: usize = NUM_SHARDS * BUCKETS_PER_SHARD;
+    /// Slots per bucket; the registry holds NUM_BUCKETS * BUCKET_SIZE
+    /// entries (256). Mirrored in the program, which checks it in a test.
+    /// Every writer returns all shards, so this is bounded by the callback
+    /// transactions the output needs (REGISTRY_WRITE_CALLBACK_TXS).
     pub const BUCKET_SIZE: usize = 16;
+    /// Pending registrations folded into the registry per queue crank.
+    pub const REGISTRATION_BATCH_SIZE: usize = 4;
 
     // ── Data Structures ─────────────────────────────────────────────────
 
@@ -34,6 +48,13 @@ mod circuits {
         pub hash: u128,
     }
 
+    /// A registrant's entry: their contact hash plus a random ownership
+    /// secret only they know, which later proves the entry is theirs.
+    pub struct RegistrationEntry {
+        pub hash: u128,
+        pub owner_secret: u128,
+    }
+
     /// Client's encrypted contact list submitted for intersection.
     pub struct ClientContacts {
         pub hashes: [u128; MAX_CLIENT_CONTACTS],
@@ -41,15 +62,51 @@ mod circuits {
     }
 
     /// Cuckoo-filter bucket holding fingerprints for the Global User Registry.
+    /// Each slot also holds its registrant's ownership secret, the number
+    /// of intersections that matched it, how many of those fell in the
+    /// current match epoch, and whether it is an authority-inserted canary.
     pub struct RegistryBucket {
         pub fingerprints: [u128; BUCKET_SIZE],
+        pub owner_secrets: [u128; BUCKET_SIZE],
+        pub hits: [u64; BUCKET_SIZE],
+        pub epoch_matches: [u64; BUCKET_SIZE],
+        pub canaries: [bool; BUCKET_SIZE],
         pub count: u64,
     }
 
-    /// The full registry state stored as MXE-encrypted shared private state.
+    /// A contiguous slice of the registry's buckets, stored as MXE-encrypted
+    /// state in its own account. Bucket `b` lives in shard
+    /// `b / BUCKETS_PER_SHARD`.
+    pub struct RegistryShard {
+        pub buckets: [RegistryBucket; BUCKETS_PER_SHARD],
+    }
+
+    /// Registry-wide counters stored as MXE-encrypted shared private state;
+    /// the buckets themselves live in the shards.
     pub struct GlobalRegistry {
-        pub buckets: [RegistryBucket; NUM_BUCKETS],
         pub total_users: u64,
+        /// Match epoch the slots' `epoch_matches` counters belong to
+        pub match_epoch: u64,
+        /// Intersections suppressed for matching more than the query cap
+        pub anomalous_queries: u64,
+        /// Intersections that matched at least one canary
+        pub canary_hits: u64,
+    }
+
+    /// The registry header and every shard, assembled inside a computation.
+    pub struct ShardedRegistry {
+        pub header: GlobalRegistry,
+        pub shards: [RegistryShard; NUM_SHARDS],
+    }
+
+    /// MXE-encrypted record of one registration, kept in the registrant's
+    /// Registration so deregistration removes exactly the slot that was
+    /// inserted: the hash together with its ownership secret.
+    pub struct RegistrationReceipt {
+        pub hash: u128,
+        pub owner_secret: u128,
+        /// False when the target bucket was full and nothing was inserted
+        pub inserted: bool,
     }
 
     /// PSI result: per-contact match flags and total match count.
@@ -62,19 +119,75 @@ mod circuits {
 
     /// Private Set Intersection: intersects client contacts against the
     /// Global User Registry. Non-matching contacts remain invisible.
+    /// Every matched slot's hit counter is incremented, so the registry
+    /// header and shards are written back alongside the result.
+    ///
+    /// Rate limiting: a slot reports at most `match_cap` matches per match
+    /// epoch; further lookups read as non-matches. The per-slot counters
+    /// are reset when `current_epoch` differs from the header's epoch.
+    ///
+    /// Enumeration guard: a query matching more than `query_cap` contacts
+    /// is answered with no matches, leaves the slots' counters untouched and
+    /// increments the header's anomaly counter.
+    ///
+    /// Canaries match like any other entry, so queriers cannot tell them
+    /// apart; a query that matches one increments the header's canary hit
+    /// counter, even when its results are suppressed. This is the only
+    /// lookup that can match a canary: check_membership and
+    /// update_registration only match the hash in the caller's receipt, and
+    /// read_my_hit_count needs the entry's ownership secret, neither of
+    /// which exists for a canary.
     ///
     /// All if/else branches are compiled to constant-time MPC selects by
     /// the arcis compiler — no secret-dependent branching leaks.
     #[instruction]
     pub fn intersect_contacts(
         client_contacts: Enc<Shared, ClientContacts>,
+        current_epoch: u64,
+        match_cap: u64,
+        query_cap: u64,
         registry: Enc<Mxe, GlobalRegistry>,
-    ) -> Enc<Shared, MatchResult> {
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> (
+        Enc<Shared, MatchResult>,
+        Enc<Mxe, GlobalRegistry>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+    ) {
         let contacts = client_contacts.to_arcis();
-        let reg = registry.to_arcis();
+        let mut header = registry.to_arcis();
+        let mut shards = [
+            shard_0.to_arcis(),
+            shard_1.to_arcis(),
+            shard_2.to_arcis(),
+            shard_3.to_arcis(),
+        ];
+
+        // Epoch rollover: every slot starts the new epoch with no matches
+        let new_epoch = header.match_epoch != current_epoch;
+        for s in 0..NUM_SHARDS {
+            for b in 0..BUCKETS_PER_SHARD {
+                for j in 0..BUCKET_SIZE {
+                    if new_epoch {
+                        shards[s].buckets[b].epoch_matches[j] = 0;
+                    }
+                }
+            }
+        }
+        if new_epoch {
+            header.match_epoch = current_epoch;
+        }
 
         let mut matched = [false; MAX_CLIENT_CONTACTS];
         let mut match_count: u64 = 0;
+        // Per-slot counter increments, undone if the query is suppressed
+        let mut added = [[[0u64; BUCKET_SIZE]; BUCKETS_PER_SHARD]; NUM_SHARDS];
+        let mut canary_hit = false;
 
         for i in 0..MAX_CLIENT_CONTACTS {
             let active = (i as u64) < contacts.count;
@@ -84,15 +197,31 @@ mod circuits {
             let b_idx = (contact_hash % (NUM_BUCKETS as u128)) as u64;
 
             let mut found = false;
-            for b in 0..NUM_BUCKETS {
-                let is_target_bucket = (b as u64) == b_idx;
-
-                for j in 0..BUCKET_SIZE {
-                    let slot_active = (j as u64) < reg.buckets[b].count;
-                    let eq = contact_hash == reg.buckets[b].fingerprints[j];
-
-                    if is_target_bucket && slot_active && eq {
-                        found = true;
+            for s in 0..NUM_SHARDS {
+                for b in 0..BUCKETS_PER_SHARD {
+                    let is_target_bucket = ((s * BUCKETS_PER_SHARD + b) as u64) == b_idx;
+
+                    for j in 0..BUCKET_SIZE {
+                        let slot_active = (j as u64) < shards[s].buckets[b].count;
+                        let eq = contact_hash == shards[s].buckets[b].fingerprints[j];
+                        let under_cap = shards[s].buckets[b].epoch_matches[j] < match_cap;
+
+                        if is_target_bucket && slot_active && eq && under_cap {
+                            found = true;
+                        }
+                        if active && is_target_bucket && slot_active && eq && under_cap {
+                            shards[s].buckets[b].hits[j] += 1;
+                            shards[s].buckets[b].epoch_matches[j] += 1;
+                            added[s][b][j] += 1;
+                        }
+                        if active
+                            && is_target_bucket
+                            && slot_active
+                            && eq
+                            && shards[s].buckets[b].canaries[j]
+                        {
+                            canary_hit = true;
+                        }
                     }
                 }
             }
@@ -103,60 +232,600 @@ mod circuits {
             }
         }
 
+        let suppressed = match_count > query_cap;
+        for i in 0..MAX_CLIENT_CONTACTS {
+            if suppressed {
+                matched[i] = false;
+            }
+        }
+        for s in 0..NUM_SHARDS {
+            for b in 0..BUCKETS_PER_SHARD {
+                for j in 0..BUCKET_SIZE {
+                    if suppressed {
+                        shards[s].buckets[b].hits[j] -= added[s][b][j];
+                        shards[s].buckets[b].epoch_matches[j] -= added[s][b][j];
+                    }
+                }
+            }
+        }
+        if suppressed {
+            match_count = 0;
+            header.anomalous_queries += 1;
+        }
+        if canary_hit {
+            header.canary_hits += 1;
+        }
+
         let result = MatchResult {
             matched,
             match_count,
         };
-        client_contacts.owner.from_arcis(result)
+        let [s0, s1, s2, s3] = shards;
+        (
+            client_contacts.owner.from_arcis(result),
+            registry.owner.from_arcis(header),
+            shard_0.owner.from_arcis(s0),
+            shard_1.owner.from_arcis(s1),
+            shard_2.owner.from_arcis(s2),
+            shard_3.owner.from_arcis(s3),
+        )
     }
 
-    // ── Registry Management ─────────────────────────────────────────────
+    /// Private membership check: whether the caller's own registration is in
+    /// the Global Registry, encrypted to the caller. Lets a registrant
+    /// confirm their insert landed, since a full bucket skips it silently.
+    /// The answer is true only when `query` is the hash in the caller's
+    /// receipt and that hash is present, so it cannot probe other hashes.
+    #[instruction]
+    pub fn check_membership(
+        query: Enc<Shared, ContactHash>,
+        receipt: Enc<Mxe, RegistrationReceipt>,
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> Enc<Shared, bool> {
+        let hash = query.to_arcis().hash;
+        let owned = receipt.to_arcis().hash == hash;
+        let shards = [
+            shard_0.to_arcis(),
+            shard_1.to_arcis(),
+            shard_2.to_arcis(),
+            shard_3.to_arcis(),
+        ];
+
+        let b_idx = (hash % (NUM_BUCKETS as u128)) as u64;
+        let mut found = false;
+        for s in 0..NUM_SHARDS {
+            for b in 0..BUCKETS_PER_SHARD {
+                let is_target_bucket = ((s * BUCKETS_PER_SHARD + b) as u64) == b_idx;
 
-    /// Register a new user's contact hash into the Global Registry.
-    /// Inserts into the appropriate bucket using constant-time writes.
-    ///
-    /// Returns the updated registry. If the target bucket is full, the
-    /// insertion is silently skipped but counters are NOT incremented
-    /// (preventing state corruption).
+                for j in 0..BUCKET_SIZE {
+                    let slot_active = (j as u64) < shards[s].buckets[b].count;
+                    let eq = hash == shards[s].buckets[b].fingerprints[j];
+
+                    if is_target_bucket && slot_active && eq {
+                        found = true;
+                    }
+                }
+            }
+        }
+
+        query.owner.from_arcis(owned && found)
+    }
+
+    /// Return how many intersections matched the caller's entry, encrypted
+    /// to the caller. The caller proves ownership with the entry's hash and
+    /// ownership secret; a claim that matches no entry reads as zero.
     #[instruction]
-    pub fn register_user(
-        user_hash: Enc<Shared, ContactHash>,
-        registry: Enc<Mxe, GlobalRegistry>,
-    ) -> Enc<Mxe, GlobalRegistry> {
-        let hash = user_hash.to_arcis();
-        let mut reg = registry.to_arcis();
+    pub fn read_my_hit_count(
+        claim: Enc<Shared, RegistrationEntry>,
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> Enc<Shared, u64> {
+        let entry = claim.to_arcis();
+        let shards = [
+            shard_0.to_arcis(),
+            shard_1.to_arcis(),
+            shard_2.to_arcis(),
+            shard_3.to_arcis(),
+        ];
+
+        let b_idx = (entry.hash % (NUM_BUCKETS as u128)) as u64;
+        let mut hits: u64 = 0;
+        for s in 0..NUM_SHARDS {
+            for b in 0..BUCKETS_PER_SHARD {
+                let is_target_bucket = ((s * BUCKETS_PER_SHARD + b) as u64) == b_idx;
 
-        let b_idx = (hash.hash % (NUM_BUCKETS as u128)) as u64;
+                for j in 0..BUCKET_SIZE {
+                    let slot_active = (j as u64) < shards[s].buckets[b].count;
+                    let owned = shards[s].buckets[b].fingerprints[j] == entry.hash
+                        && shards[s].buckets[b].owner_secrets[j] == entry.owner_secret;
+
+                    if is_target_bucket && slot_active && owned {
+                        hits = shards[s].buckets[b].hits[j];
+                    }
+                }
+            }
+        }
+
+        claim.owner.from_arcis(hits)
+    }
+
+    // ── Registry Management ─────────────────────────────────────────────
+
+    /// Insert `hash` and its `owner_secret` into its bucket, with zero hit
+    /// and epoch match counts, using constant-time writes. Canaries are
+    /// flagged and not counted in `total_users`. Nothing changes when
+    /// `active` is false or the target bucket is full; in the latter case
+    /// counters are NOT incremented (preventing state corruption). Returns
+    /// the registry and whether the hash was inserted.
+    fn insert_hash(
+        mut reg: ShardedRegistry,
+        hash: u128,
+        owner_secret: u128,
+        canary: bool,
+        active: bool,
+    ) -> (ShardedRegistry, bool) {
+        let b_idx = (hash % (NUM_BUCKETS as u128)) as u64;
 
         // Check if target bucket has space BEFORE attempting insertion
         let mut insertion_succeeded = false;
 
-        for b in 0..NUM_BUCKETS {
-            let is_target = (b as u64) == b_idx;
-            let insert_pos = reg.buckets[b].count;
-            let has_space = insert_pos < (BUCKET_SIZE as u64);
+        for s in 0..NUM_SHARDS {
+            for b in 0..BUCKETS_PER_SHARD {
+                let is_target = active && ((s * BUCKETS_PER_SHARD + b) as u64) == b_idx;
+                let insert_pos = reg.shards[s].buckets[b].count;
+                let has_space = insert_pos < (BUCKET_SIZE as u64);
+
+                for j in 0..BUCKET_SIZE {
+                    let is_insert_slot = (j as u64) == insert_pos;
+
+                    if is_target && is_insert_slot && has_space {
+                        reg.shards[s].buckets[b].fingerprints[j] = hash;
+                        reg.shards[s].buckets[b].owner_secrets[j] = owner_secret;
+                        reg.shards[s].buckets[b].hits[j] = 0;
+                        reg.shards[s].buckets[b].epoch_matches[j] = 0;
+                        reg.shards[s].buckets[b].canaries[j] = canary;
+                        insertion_succeeded = true;
+                    }
+                }
 
-            for j in 0..BUCKET_SIZE {
-                let is_insert_slot = (j as u64) == insert_pos;
+                // Only increment if we actually inserted (prevents silent overflow)
+                if is_target && has_space {
+                    reg.shards[s].buckets[b].count += 1;
+                }
+            }
+        }
+
+        // Only increment total if a real registrant was inserted
+        if insertion_succeeded && !canary {
+            reg.header.total_users += 1;
+        }
+
+        (reg, insertion_succeeded)
+    }
+
+    /// Remove the registrant slot holding `hash` with `owner_secret` from its
+    /// bucket using constant-time writes: the last active slot (hash, secret
+    /// and counters) is moved into the vacated one. Canary slots and slots
+    /// with another owner's secret never match, so a colliding or shared
+    /// hash cannot evict them. Nothing changes when `active` is false or no
+    /// slot matches.
+    fn remove_hash(
+        mut reg: ShardedRegistry,
+        hash: u128,
+        owner_secret: u128,
+        active: bool,
+    ) -> ShardedRegistry {
+        let b_idx = (hash % (NUM_BUCKETS as u128)) as u64;
+        let mut removed = false;
+
+        for s in 0..NUM_SHARDS {
+            for b in 0..BUCKETS_PER_SHARD {
+                let is_target = active && ((s * BUCKETS_PER_SHARD + b) as u64) == b_idx;
+                let count = reg.shards[s].buckets[b].count;
+
+                // Locate the owner's slot and the contents of the last active slot
+                let mut found = false;
+                let mut pos: u64 = 0;
+                let mut last_value: u128 = 0;
+                let mut last_secret: u128 = 0;
+                let mut last_hits: u64 = 0;
+                let mut last_epoch_matches: u64 = 0;
+                let mut last_canary = false;
+                for j in 0..BUCKET_SIZE {
+                    let slot_active = (j as u64) < count;
+                    let owned = reg.shards[s].buckets[b].fingerprints[j] == hash
+                        && reg.shards[s].buckets[b].owner_secrets[j] == owner_secret
+                        && !reg.shards[s].buckets[b].canaries[j];
+                    if slot_active && !found && owned {
+                        found = true;
+                        pos = j as u64;
+                    }
+                    if (j as u64) + 1 == count {
+                        last_value = reg.shards[s].buckets[b].fingerprints[j];
+                        last_secret = reg.shards[s].buckets[b].owner_secrets[j];
+                        last_hits = reg.shards[s].buckets[b].hits[j];
+                        last_epoch_matches = reg.shards[s].buckets[b].epoch_matches[j];
+                        last_canary = reg.shards[s].buckets[b].canaries[j];
+                    }
+                }
 
-                if is_target && is_insert_slot && has_space {
-                    reg.buckets[b].fingerprints[j] = hash.hash;
-                    insertion_succeeded = true;
+                let remove = is_target && found;
+                for j in 0..BUCKET_SIZE {
+                    if remove && (j as u64) == pos {
+                        reg.shards[s].buckets[b].fingerprints[j] = last_value;
+                        reg.shards[s].buckets[b].owner_secrets[j] = last_secret;
+                        reg.shards[s].buckets[b].hits[j] = last_hits;
+                        reg.shards[s].buckets[b].epoch_matches[j] = last_epoch_matches;
+                        reg.shards[s].buckets[b].canaries[j] = last_canary;
+                    }
+                    if remove && (j as u64) + 1 == count {
+                        reg.shards[s].buckets[b].fingerprints[j] = 0;
+                        reg.shards[s].buckets[b].owner_secrets[j] = 0;
+                        reg.shards[s].buckets[b].hits[j] = 0;
+                        reg.shards[s].buckets[b].epoch_matches[j] = 0;
+                        reg.shards[s].buckets[b].canaries[j] = false;
+                    }
+                }
+                if remove {
+                    reg.shards[s].buckets[b].count -= 1;
+                    removed = true;
                 }
             }
+        }
+
+        if removed {
+            reg.header.total_users -= 1;
+        }
+
+        reg
+    }
+
+    /// Replace the registrant slot holding `old` with `old_secret` (matched
+    /// as in `remove_hash`) with `new` and its `owner_secret`, with a fresh
+    /// hit count, using constant-time writes. Applies only when `active`,
+    /// that slot is present and `new`'s bucket has room once it is gone;
+    /// otherwise nothing changes. Returns the registry and whether the
+    /// update was applied.
+    fn replace_hash(
+        reg: ShardedRegistry,
+        old: u128,
+        old_secret: u128,
+        new: u128,
+        owner_secret: u128,
+        active: bool,
+    ) -> (ShardedRegistry, bool) {
+        let old_idx = (old % (NUM_BUCKETS as u128)) as u64;
+        let new_idx = (new % (NUM_BUCKETS as u128)) as u64;
+
+        // Decide before writing, so a rejected update leaves no trace
+        let mut present = false;
+        let mut has_space = false;
+        for s in 0..NUM_SHARDS {
+            for b in 0..BUCKETS_PER_SHARD {
+                let idx = (s * BUCKETS_PER_SHARD + b) as u64;
+                let count = reg.shards[s].buckets[b].count;
+
+                for j in 0..BUCKET_SIZE {
+                    let slot_active = (j as u64) < count;
+                    if idx == old_idx
+                        && slot_active
+                        && reg.shards[s].buckets[b].fingerprints[j] == old
+                        && reg.shards[s].buckets[b].owner_secrets[j] == old_secret
+                        && !reg.shards[s].buckets[b].canaries[j]
+                    {
+                        present = true;
+                    }
+                }
 
-            // Only increment if we actually inserted (prevents silent overflow)
-            if is_target && has_space {
-                reg.buckets[b].count += 1;
+                // Removing `old` frees a slot when both share a bucket
+                if idx == new_idx && (count < (BUCKET_SIZE as u64) || new_idx == old_idx) {
+                    has_space = true;
+                }
             }
         }
 
-        // Only increment total if insertion succeeded
-        if insertion_succeeded {
-            reg.total_users += 1;
+        let apply = active && present && has_space;
+        let reg = remove_hash(reg, old, old_secret, apply);
+        let (reg, _) = insert_hash(reg, new, owner_secret, false, apply);
+        (reg, apply)
+    }
+
+    /// Register a new user's contact hash into the Global Registry.
+    /// Inserts into the appropriate bucket using constant-time writes.
+    ///
+    /// `attested` is the attestation service's own encryption of the hash
+    /// it verified; the entry is inserted only when both hashes agree, so a
+    /// signed attestation cannot be paired with another ciphertext.
+    ///
+    /// Returns the updated header and every shard, the registrant's
+    /// receipt, and whether the attested hash matched. If the target bucket
+    /// is full, the insertion is silently skipped (see `insert_hash`) and
+    /// the receipt records it.
+    #[instruction]
+    pub fn register_user(
+        user_entry: Enc<Shared, RegistrationEntry>,
+        attested: Enc<Shared, ContactHash>,
+        registry: Enc<Mxe, GlobalRegistry>,
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> (
+        Enc<Mxe, GlobalRegistry>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistrationReceipt>,
+        bool,
+    ) {
+        let entry = user_entry.to_arcis();
+        let accepted = attested.to_arcis().hash == entry.hash;
+        let reg = ShardedRegistry {
+            header: registry.to_arcis(),
+            shards: [
+                shard_0.to_arcis(),
+                shard_1.to_arcis(),
+                shard_2.to_arcis(),
+                shard_3.to_arcis(),
+            ],
+        };
+        let (reg, inserted) = insert_hash(reg, entry.hash, entry.owner_secret, false, accepted);
+        let receipt = RegistrationReceipt {
+            hash: entry.hash,
+            owner_secret: entry.owner_secret,
+            inserted,
+        };
+
+        let ShardedRegistry {
+            header,
+            shards: [s0, s1, s2, s3],
+        } = reg;
+        (
+            registry.owner.from_arcis(header),
+            shard_0.owner.from_arcis(s0),
+            shard_1.owner.from_arcis(s1),
+            shard_2.owner.from_arcis(s2),
+            shard_3.owner.from_arcis(s3),
+            Mxe::get().from_arcis(receipt),
+            accepted.reveal(),
+        )
+    }
+
+    /// Fold up to REGISTRATION_BATCH_SIZE queued registrations into the
+    /// Global Registry in one computation. Each entry is encrypted under its
+    /// registrant's own key; only the first `pending` entries are inserted,
+    /// the rest are padding and leave the registry untouched. Entry `i` is
+    /// inserted only when its hash matches `attested_i`, as in
+    /// `register_user`. Returns one receipt per batch slot after the
+    /// registry, then whether each slot's attested hash matched; padding
+    /// results are discarded by the program.
+    #[instruction]
+    pub fn process_registration_queue(
+        entry_0: Enc<Shared, RegistrationEntry>,
+        entry_1: Enc<Shared, RegistrationEntry>,
+        entry_2: Enc<Shared, RegistrationEntry>,
+        entry_3: Enc<Shared, RegistrationEntry>,
+        attested_0: Enc<Shared, ContactHash>,
+        attested_1: Enc<Shared, ContactHash>,
+        attested_2: Enc<Shared, ContactHash>,
+        attested_3: Enc<Shared, ContactHash>,
+        pending: u64,
+        registry: Enc<Mxe, GlobalRegistry>,
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> (
+        Enc<Mxe, GlobalRegistry>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistrationReceipt>,
+        Enc<Mxe, RegistrationReceipt>,
+        Enc<Mxe, RegistrationReceipt>,
+        Enc<Mxe, RegistrationReceipt>,
+        bool,
+        bool,
+        bool,
+        bool,
+    ) {
+        let entries = [
+            entry_0.to_arcis(),
+            entry_1.to_arcis(),
+            entry_2.to_arcis(),
+            entry_3.to_arcis(),
+        ];
+        let hashes = [
+            entries[0].hash,
+            entries[1].hash,
+            entries[2].hash,
+            entries[3].hash,
+        ];
+        let accepted = [
+            attested_0.to_arcis().hash == hashes[0],
+            attested_1.to_arcis().hash == hashes[1],
+            attested_2.to_arcis().hash == hashes[2],
+            attested_3.to_arcis().hash == hashes[3],
+        ];
+        let mut reg = ShardedRegistry {
+            header: registry.to_arcis(),
+            shards: [
+                shard_0.to_arcis(),
+                shard_1.to_arcis(),
+                shard_2.to_arcis(),
+                shard_3.to_arcis(),
+            ],
+        };
+
+        let mut inserted = [false; REGISTRATION_BATCH_SIZE];
+        for i in 0..REGISTRATION_BATCH_SIZE {
+            let (next, ok) = insert_hash(
+                reg,
+                hashes[i],
+                entries[i].owner_secret,
+                false,
+                (i as u64) < pending && accepted[i],
+            );
+            reg = next;
+            inserted[i] = ok;
         }
 
-        registry.owner.from_arcis(reg)
+        let ShardedRegistry {
+            header,
+            shards: [s0, s1, s2, s3],
+        } = reg;
+        (
+            registry.owner.from_arcis(header),
+            shard_0.owner.from_arcis(s0),
+            shard_1.owner.from_arcis(s1),
+            shard_2.owner.from_arcis(s2),
+            shard_3.owner.from_arcis(s3),
+            Mxe::get().from_arcis(RegistrationReceipt {
+                hash: hashes[0],
+                owner_secret: entries[0].owner_secret,
+                inserted: inserted[0],
+            }),
+            Mxe::get().from_arcis(RegistrationReceipt {
+                hash: hashes[1],
+                owner_secret: entries[1].owner_secret,
+                inserted: inserted[1],
+            }),
+            Mxe::get().from_arcis(RegistrationReceipt {
+                hash: hashes[2],
+                owner_secret: entries[2].owner_secret,
+                inserted: inserted[2],
+            }),
+            Mxe::get().from_arcis(RegistrationReceipt {
+                hash: hashes[3],
+                owner_secret: entries[3].owner_secret,
+                inserted: inserted[3],
+            }),
+            accepted[0].reveal(),
+            accepted[1].reveal(),
+            accepted[2].reveal(),
+            accepted[3].reveal(),
+        )
+    }
+
+    /// Remove a registration from the Global Registry using the receipt
+    /// stored when it was inserted, so a caller can only remove their own
+    /// slot: the hash with their ownership secret, never a canary or
+    /// another registrant sharing the hash. Receipts of insertions that
+    /// were skipped remove nothing.
+    #[instruction]
+    pub fn deregister_user(
+        receipt: Enc<Mxe, RegistrationReceipt>,
+        registry: Enc<Mxe, GlobalRegistry>,
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> (
+        Enc<Mxe, GlobalRegistry>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+    ) {
+        let receipt = receipt.to_arcis();
+        let reg = ShardedRegistry {
+            header: registry.to_arcis(),
+            shards: [
+                shard_0.to_arcis(),
+                shard_1.to_arcis(),
+                shard_2.to_arcis(),
+                shard_3.to_arcis(),
+            ],
+        };
+        let reg = remove_hash(reg, receipt.hash, receipt.owner_secret, receipt.inserted);
+
+        let ShardedRegistry {
+            header,
+            shards: [s0, s1, s2, s3],
+        } = reg;
+        (
+            registry.owner.from_arcis(header),
+            shard_0.owner.from_arcis(s0),
+            shard_1.owner.from_arcis(s1),
+            shard_2.owner.from_arcis(s2),
+            shard_3.owner.from_arcis(s3),
+        )
+    }
+
+    /// Replace a registrant's hash (e.g. after a phone number change) in one
+    /// computation. The old slot comes from the receipt stored at
+    /// registration, so a caller can only replace their own; the new entry
+    /// (hash and ownership secret) is encrypted by the registrant and must
+    /// match `attested`, as in `register_user`. Fails atomically, leaving
+    /// the registry and receipt unchanged, when the old hash is not present,
+    /// the new bucket is full or the attested hash differs. Returns the
+    /// registry, the receipt to keep, and whether the update was applied.
+    #[instruction]
+    pub fn update_registration(
+        receipt: Enc<Mxe, RegistrationReceipt>,
+        new_entry: Enc<Shared, RegistrationEntry>,
+        attested: Enc<Shared, ContactHash>,
+        registry: Enc<Mxe, GlobalRegistry>,
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> (
+        Enc<Mxe, GlobalRegistry>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistrationReceipt>,
+        bool,
+    ) {
+        let old = receipt.to_arcis();
+        let new = new_entry.to_arcis();
+        let accepted = attested.to_arcis().hash == new.hash;
+        let reg = ShardedRegistry {
+            header: registry.to_arcis(),
+            shards: [
+                shard_0.to_arcis(),
+                shard_1.to_arcis(),
+                shard_2.to_arcis(),
+                shard_3.to_arcis(),
+            ],
+        };
+        let (reg, updated) = replace_hash(
+            reg,
+            old.hash,
+            old.owner_secret,
+            new.hash,
+            new.owner_secret,
+            old.inserted && accepted,
+        );
+
+        let mut kept = old;
+        if updated {
+            kept.hash = new.hash;
+            kept.owner_secret = new.owner_secret;
+        }
+
+        let ShardedRegistry {
+            header,
+            shards: [s0, s1, s2, s3],
+        } = reg;
+        (
+            registry.owner.from_arcis(header),
+            shard_0.owner.from_arcis(s0),
+            shard_1.owner.from_arcis(s1),
+            shard_2.owner.from_arcis(s2),
+            shard_3.owner.from_arcis(s3),
+            Mxe::get().from_arcis(kept),
+            updated.reveal(),
+        )
     }
 
     /// Reveal the total number of registered users (public statistic).
@@ -166,32 +835,143 @@ mod circuits {
         reg.total_users.reveal()
     }
 
-    /// Bootstrap the Global Registry with MXE-encrypted empty state.
-    /// Must be called once before any register_user or intersect_contacts.
-    /// Creates a properly encrypted zero-state that MPC nodes can decrypt.
+    /// Return how many intersections were suppressed by the per-query match
+    /// cap, encrypted to `authority` so only the registry authority can read
+    /// it. Only the aggregate count is returned, never which queries.
     #[instruction]
-    pub fn init_registry() -> Enc<Mxe, GlobalRegistry> {
-        let registry = GlobalRegistry {
+    pub fn reveal_anomaly_count(
+        authority: Shared,
+        registry: Enc<Mxe, GlobalRegistry>,
+    ) -> Enc<Shared, u64> {
+        let reg = registry.to_arcis();
+        authority.from_arcis(reg.anomalous_queries)
+    }
+
+    // ── Canaries ────────────────────────────────────────────────────────
+
+    /// Insert an authority-chosen canary fingerprint (with a random
+    /// ownership secret, like a real registrant's) into its bucket. The
+    /// slot is flagged as a canary, so queries matching it are counted in
+    /// `canary_hits`, but it is stored and matched exactly like a real
+    /// entry. Returns the updated registry and whether the bucket had room,
+    /// encrypted to the inserting authority.
+    #[instruction]
+    pub fn insert_canary(
+        canary_entry: Enc<Shared, RegistrationEntry>,
+        registry: Enc<Mxe, GlobalRegistry>,
+        shard_0: Enc<Mxe, RegistryShard>,
+        shard_1: Enc<Mxe, RegistryShard>,
+        shard_2: Enc<Mxe, RegistryShard>,
+        shard_3: Enc<Mxe, RegistryShard>,
+    ) -> (
+        Enc<Mxe, GlobalRegistry>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Shared, bool>,
+    ) {
+        let entry = canary_entry.to_arcis();
+        let reg = ShardedRegistry {
+            header: registry.to_arcis(),
+            shards: [
+                shard_0.to_arcis(),
+                shard_1.to_arcis(),
+                shard_2.to_arcis(),
+                shard_3.to_arcis(),
+            ],
+        };
+        let (reg, inserted) = insert_hash(reg, entry.hash, entry.owner_secret, true, true);
+
+        let ShardedRegistry {
+            header,
+            shards: [s0, s1, s2, s3],
+        } = reg;
+        (
+            registry.owner.from_arcis(header),
+            shard_0.owner.from_arcis(s0),
+            shard_1.owner.from_arcis(s1),
+            shard_2.owner.from_arcis(s2),
+            shard_3.owner.from_arcis(s3),
+            canary_entry.owner.from_arcis(inserted),
+        )
+    }
+
+    /// Return the number of intersections that matched a canary, encrypted
+    /// to `authority` so only the registry authority can read it.
+    #[instruction]
+    pub fn reveal_canary_hits(
+        authority: Shared,
+        registry: Enc<Mxe, GlobalRegistry>,
+    ) -> Enc<Shared, u64> {
+        let reg = registry.to_arcis();
+        authority.from_arcis(reg.canary_hits)
+    }
+
+    /// An empty shard: every bucket zeroed.
+    fn empty_shard() -> RegistryShard {
+        RegistryShard {
             buckets: [
                 RegistryBucket {
                     fingerprints: [0u128; BUCKET_SIZE],
+                    owner_secrets: [0u128; BUCKET_SIZE],
+                    hits: [0u64; BUCKET_SIZE],
+                    epoch_matches: [0u64; BUCKET_SIZE],
+                    canaries: [false; BUCKET_SIZE],
                     count: 0,
                 },
                 RegistryBucket {
                     fingerprints: [0u128; BUCKET_SIZE],
+                    owner_secrets: [0u128; BUCKET_SIZE],
+                    hits: [0u64; BUCKET_SIZE],
+                    epoch_matches: [0u64; BUCKET_SIZE],
+                    canaries: [false; BUCKET_SIZE],
                     count: 0,
                 },
                 RegistryBucket {
                     fingerprints: [0u128; BUCKET_SIZE],
+                    owner_secrets: [0u128; BUCKET_SIZE],
+                    hits: [0u64; BUCKET_SIZE],
+                    epoch_matches: [0u64; BUCKET_SIZE],
+                    canaries: [false; BUCKET_SIZE],
                     count: 0,
                 },
                 RegistryBucket {
                     fingerprints: [0u128; BUCKET_SIZE],
+                    owner_secrets: [0u128; BUCKET_SIZE],
+                    hits: [0u64; BUCKET_SIZE],
+                    epoch_matches: [0u64; BUCKET_SIZE],
+                    canaries: [false; BUCKET_SIZE],
                     count: 0,
                 },
             ],
+        }
+    }
+
+    /// Bootstrap the Global Registry with MXE-encrypted empty state.
+    /// Must be called once before any register_user or intersect_contacts.
+    /// Creates a properly encrypted zero-state that MPC nodes can decrypt:
+    /// the header followed by one empty shard per shard account.
+    #[instruction]
+    pub fn init_registry() -> (
+        Enc<Mxe, GlobalRegistry>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+        Enc<Mxe, RegistryShard>,
+    ) {
+        let registry = GlobalRegistry {
             total_users: 0,
+            match_epoch: 0,
+            anomalous_queries: 0,
+            canary_hits: 0,
         };
-        Mxe::get().from_arcis(registry)
+        (
+            Mxe::get().from_arcis(registry),
+            Mxe::get().from_arcis(empty_shard()),
+            Mxe::get().from_arcis(empty_shard()),
+            Mxe::get().from_arcis(empty_shard()),
+            Mxe::get().from_arcis(empty_shard()),
+        )
     }
 }
@@ -7,21 +7,37 @@
 // ============================================================================
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::system_program;
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
 use arcium_anchor::comp_def_offset;
 use arcium_anchor::prelude::*;
 use arcium_client::idl::arcium::types::CallbackAccount;
 
+// The program's error enum; shadows anchor_lang's prelude ErrorCode for the
+// state helpers defined outside the program module.
+use crate::blind_link::{ErrorCode, RegistryTransitionEvent};
+
+#[cfg(not(target_os = "solana"))]
+pub mod transparency;
+
 declare_id!("88vVM7s7TKCPeJ8DNHnctTTTWFWwSmSDAxnuTRjuzTyn");
 
 // ── State Accounts ──────────────────────────────────────────────────────
 
-/// Global registry account storing MXE-encrypted user fingerprints.
-/// Bucket layout: 4 buckets × 16 entries per bucket = 64 max users.
-/// Actual capacity may be less due to hash collisions in bucket assignment.
+/// Global registry account: MXE-encrypted registry-wide counters plus the
+/// registry's administration and write-coordination state. The user
+/// fingerprints live in NUM_REGISTRY_SHARDS `RegistryShard` accounts.
 #[account]
 pub struct RegistryState {
     pub bump: u8,
-    /// MXE-encrypted bucket data (4 buckets × 16 slots × u128 fingerprints + counts)
+    /// MXE-encrypted GlobalRegistry header (total user count)
     /// Layout serialized by Arcium MXE during computation callbacks
     pub encrypted_data: Vec<u8>,
     /// Encryption nonce for MXE state
@@ -30,10 +46,240 @@ pub struct RegistryState {
     pub authority: Pubkey,
     /// Total PSI queries processed (not user count; that's encrypted in MXE)
     pub computation_count: u64,
+    /// Authority nominated via transfer_authority, awaiting accept_authority
+    pub pending_authority: Option<Pubkey>,
+    /// Bitmask of paused operations (PAUSE_REGISTRATION | PAUSE_DISCOVERY)
+    pub paused: u8,
+    /// Set by init_registry_callback once the MXE-encrypted empty state is stored
+    pub bootstrapped: bool,
+    /// Set by arm_registry_reset; permits one re-bootstrap of a live registry
+    pub reset_armed: bool,
+    /// Seconds after `PsiSession.attempt_started_at` before expire_session
+    /// may reap it
+    pub session_timeout_secs: i64,
+    /// Registry computations (register, bootstrap, reveal) whose callback failed
+    pub failed_computations: u64,
+    /// FAILURE_* code of the most recent failed registry computation
+    pub last_failure_reason: u8,
+    /// Offset of the queued computation that will rewrite `encrypted_data`.
+    /// Only one writer may be in flight, so no callback can overwrite state
+    /// produced by another writer it never saw.
+    pub write_lock_offset: Option<u64>,
+    /// Timestamp at which the write lock was taken
+    pub write_locked_at: i64,
+    /// Incremented every time a callback writes `encrypted_data`
+    pub version: u64,
+    /// Commitment to the header and every shard at `version`
+    /// (see `registry_snapshot_hash`)
+    pub state_hash: [u8; 32],
+    /// Head of the transparency hash chain over every state transition
+    /// (see `transition_link`); all zeros before the first write
+    pub transition_head: [u8; 32],
+    /// Ed25519 key of the identifier-verification service that must attest
+    /// every registration (see `attestation_message`); None disables it
+    pub attestation_key: Option<Pubkey>,
+    /// Matches each registry entry may report per match epoch; further
+    /// lookups read as non-matches. 0 disables the cap
+    pub match_cap_per_epoch: u64,
+    /// Length of a match epoch in seconds
+    pub match_epoch_secs: i64,
+    /// Most contacts one intersection may match; queries over it are
+    /// answered with no matches and counted as anomalies. 0 disables it
+    pub max_matches_per_query: u64,
+    /// Offset of the last writer whose lock was reclaimed as stale, so its
+    /// late callback can report which computation it discarded
+    pub reclaimed_lock_offset: Option<u64>,
+    /// Offset of the most recently queued reveal_registry_size
+    pub reveal_offset: Option<u64>,
+}
+
+impl RegistryState {
+    /// Take the registry write lock for `computation_offset`. Fails while
+    /// another writer is in flight, unless its lock has outlived the
+    /// session timeout (its callback is then treated as lost).
+    fn acquire_write_lock(&mut self, computation_offset: u64, now: i64) -> Result<()> {
+        if let Some(holder) = self.write_lock_offset {
+            let stale = now
+                >= self
+                    .write_locked_at
+                    .saturating_add(self.session_timeout_secs);
+            require!(stale, ErrorCode::RegistryWriteLocked);
+            msg!(
+                "Blind-Link: Reclaiming stale registry write lock (offset: {})",
+                holder
+            );
+            self.reclaimed_lock_offset = Some(holder);
+        }
+        self.write_lock_offset = Some(computation_offset);
+        self.write_locked_at = now;
+        Ok(())
+    }
+
+    /// Whether `computation_account` is the computation holding the write
+    /// lock. Registry-writing callbacks only touch state when it is.
+    fn holds_write_lock(
+        &self,
+        computation_account: Pubkey,
+        mxe_account: &MXEAccount,
+    ) -> Result<bool> {
+        match self.write_lock_offset {
+            Some(offset) => Ok(computation_account
+                == derive_comp_pda!(offset, mxe_account, ErrorCode::ClusterNotSet)),
+            None => Ok(false),
+        }
+    }
+
+    /// Offset of a discarded writer's computation, from the lock it lost to
+    /// acquire_write_lock; 0 if that lock has since been reclaimed again.
+    fn reclaimed_offset(
+        &self,
+        computation_account: Pubkey,
+        mxe_account: &MXEAccount,
+    ) -> Result<u64> {
+        computation_offset_of(self.reclaimed_lock_offset, computation_account, mxe_account)
+    }
+
+    /// Release the write lock, returning the offset of the computation
+    /// that held it.
+    fn release_write_lock(&mut self) -> u64 {
+        self.write_lock_offset.take().unwrap_or_default()
+    }
+
+    /// Take the write lock away from `computation_offset` once it is known
+    /// to have timed out, as acquire_write_lock would, so its late callback
+    /// takes the stale path. No-op if another computation holds the lock.
+    fn abandon_write_lock(&mut self, computation_offset: u64) {
+        if self.write_lock_offset == Some(computation_offset) {
+            self.reclaimed_lock_offset = self.write_lock_offset.take();
+        }
+    }
+
+    /// Store new MXE-encrypted header state from a verified callback,
+    /// advance the version and state commitment, and extend the
+    /// transparency log with a RegistryTransitionEvent. `shard_hashes` are
+    /// the commitments of the shards the same callback wrote back.
+    fn commit_state(
+        &mut self,
+        ciphertexts: &[[u8; 32]],
+        nonce: u128,
+        shard_hashes: &[[u8; 32]],
+        computation_offset: u64,
+    ) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        let prev_head = self.transition_head;
+
+        self.encrypted_data = ciphertexts.iter().flat_map(|c| c.to_vec()).collect();
+        self.nonce = nonce;
+        self.version += 1;
+        self.state_hash = registry_snapshot_hash(
+            &registry_state_hash(&self.encrypted_data, nonce),
+            shard_hashes,
+        );
+        self.transition_head =
+            transition_link(&prev_head, &self.state_hash, computation_offset, slot);
+
+        emit!(RegistryTransitionEvent {
+            version: self.version,
+            prev_head,
+            state_hash: self.state_hash,
+            computation_offset,
+            slot,
+            new_head: self.transition_head,
+        });
+        Ok(())
+    }
+
+    /// Current match epoch and per-entry match cap, as passed to
+    /// intersect_contacts. An uncapped registry passes u64::MAX.
+    fn match_limit(&self, now: i64) -> (u64, u64) {
+        let epoch = now.max(0) as u64 / self.match_epoch_secs.max(1) as u64;
+        let cap = match self.match_cap_per_epoch {
+            0 => u64::MAX,
+            cap => cap,
+        };
+        (epoch, cap)
+    }
+
+    /// Per-query match cap as passed to intersect_contacts; u64::MAX when
+    /// disabled.
+    fn query_match_cap(&self) -> u64 {
+        match self.max_matches_per_query {
+            0 => u64::MAX,
+            cap => cap,
+        }
+    }
+
+    /// Record a failed registry computation. Callbacks persist this and
+    /// return Ok so the write is not rolled back with the transaction.
+    fn record_failure(&mut self, reason: u8) {
+        self.failed_computations += 1;
+        self.last_failure_reason = reason;
+    }
+
+    /// Account size: discriminator + fields, with `encrypted_data` sized for
+    /// the circuit's GlobalRegistry header.
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 4 + REGISTRY_HEADER_CIPHERTEXTS * CIPHERTEXT_SIZE // encrypted_data
+        + 16 // nonce
+        + 32 // authority
+        + 8 // computation_count
+        + 1 + 32 // pending_authority
+        + 1 // paused
+        + 1 // bootstrapped
+        + 1 // reset_armed
+        + 8 // session_timeout_secs
+        + 8 // failed_computations
+        + 1 // last_failure_reason
+        + 1 + 8 // write_lock_offset
+        + 8 // write_locked_at
+        + 8 // version
+        + 32 // state_hash
+        + 32 // transition_head
+        + 1 + 32 // attestation_key
+        + 8 // match_cap_per_epoch
+        + 8 // match_epoch_secs
+        + 8 // max_matches_per_query
+        + 1 + 8 // reclaimed_lock_offset
+        + 1 + 8; // reveal_offset
+}
+
+/// One slice of the Global Registry's buckets (BUCKETS_PER_SHARD of them),
+/// MXE-encrypted. Every registry computation reads all shards, and every
+/// registry-writing callback writes all of them back.
+#[account]
+pub struct RegistryShard {
+    pub bump: u8,
+    /// MXE-encrypted RegistryShard circuit struct
+    pub encrypted_data: Vec<u8>,
+    /// Encryption nonce for this shard's MXE state
+    pub nonce: u128,
+    /// Position of the shard; bucket `b` lives in shard `b / BUCKETS_PER_SHARD`
+    pub index: u8,
+}
+
+impl RegistryShard {
+    /// Account size: discriminator + fields, with `encrypted_data` sized for
+    /// the circuit's RegistryShard.
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 4 + REGISTRY_SHARD_CIPHERTEXTS * CIPHERTEXT_SIZE // encrypted_data
+        + 16 // nonce
+        + 1; // index
+
+    /// Size a shard is created with: accounts created through CPI are
+    /// capped at MAX_PERMITTED_DATA_INCREASE, so resize_registry_shard
+    /// grows it the rest of the way.
+    const INIT_SPACE: usize = if Self::SPACE < MAX_PERMITTED_DATA_INCREASE {
+        Self::SPACE
+    } else {
+        MAX_PERMITTED_DATA_INCREASE
+    };
 }
 
 /// Per-session account tracking an active PSI computation.
-/// Created when a user initiates contact intersection, closed on callback.
+/// Created when a user initiates contact intersection, closed by the user
+/// via close_session (after reading the result) or cancel_session.
 #[account]
 pub struct PsiSession {
     pub bump: u8,
@@ -45,16 +291,1010 @@ pub struct PsiSession {
     pub result_ciphertext: Vec<u8>,
     /// Result nonce for client-side decryption
     pub result_nonce: [u8; 16],
-    /// Session status: 0 = pending, 1 = computing, 2 = completed, 3 = failed
-    pub status: u8,
+    /// Lifecycle state; changed only through `PsiSession::transition` (or
+    /// `PsiSession::settle` in callbacks)
+    pub status: SessionStatus,
+    /// Timestamp of session creation
+    pub created_at: i64,
+    /// Timestamp at which the active computation was queued; the session
+    /// timeout runs from here
+    pub attempt_started_at: i64,
+    /// FAILURE_* code explaining why the session failed (FAILURE_NONE otherwise)
+    pub failure_reason: u8,
+    /// Offset of the computation currently queued for this session; equals
+    /// `computation_offset` until the first retry_intersect
+    pub active_computation_offset: u64,
+    /// Number of retry_intersect calls made for this session
+    pub retry_count: u8,
+    /// Failed attempts superseded by a retry, oldest first
+    pub retry_history: Vec<RetryRecord>,
+    /// `RegistryState.version` current when the active computation was queued
+    pub registry_version: u64,
+}
+
+impl PsiSession {
+    /// Move the session to `next`, rejecting transitions the lifecycle
+    /// does not allow. Terminal states report SessionAlreadyComplete.
+    fn transition(&mut self, next: SessionStatus) -> Result<()> {
+        self.status.transition(next)
+    }
+
+    /// Settle the session with a callback's outcome. Unlike `transition`
+    /// this never fails: the callback also releases the registry write
+    /// lock, so it must not roll back. A session that already left
+    /// Computing (cancelled or expired meanwhile) keeps its status, and
+    /// false is returned so the callback drops the outcome.
+    fn settle(&mut self, next: SessionStatus, failure_reason: u8) -> bool {
+        if !self.status.settle(next) {
+            return false;
+        }
+        self.failure_reason = failure_reason;
+        true
+    }
+
+    /// Mark a Computing session whose callback has not arrived within
+    /// `timeout_secs` of the active attempt as Expired. Fails before the
+    /// deadline.
+    fn expire(&mut self, now: i64, timeout_secs: i64) -> Result<()> {
+        require!(
+            self.status == SessionStatus::Computing,
+            ErrorCode::SessionNotComputing
+        );
+        require!(
+            now >= self.attempt_started_at.saturating_add(timeout_secs),
+            ErrorCode::SessionNotExpired
+        );
+        self.transition(SessionStatus::Expired)?;
+        self.failure_reason = FAILURE_TIMEOUT;
+        Ok(())
+    }
+
+    /// Whether retry_intersect can no longer re-queue this session.
+    fn retries_exhausted(&self) -> bool {
+        (self.retry_count as usize) >= MAX_PSI_RETRIES
+    }
+
+    /// Supersede the failed or timed-out attempt with `computation_offset`,
+    /// recording it in the retry history. A Computing session past the
+    /// timeout is expired first, so a retry and a concurrent expire_session
+    /// leave the session in the same state whichever lands first.
+    fn begin_retry(
+        &mut self,
+        computation_offset: u64,
+        now: i64,
+        timeout_secs: i64,
+        registry_version: u64,
+    ) -> Result<()> {
+        if self.status == SessionStatus::Computing {
+            self.expire(now, timeout_secs)?;
+        }
+        require!(
+            matches!(self.status, SessionStatus::Failed | SessionStatus::Expired),
+            ErrorCode::SessionNotRetryable
+        );
+        require!(!self.retries_exhausted(), ErrorCode::RetryLimitReached);
+
+        self.retry_history.push(RetryRecord {
+            computation_offset: self.active_computation_offset,
+            failure_reason: self.failure_reason,
+            retried_at: now,
+        });
+        self.retry_count += 1;
+        self.active_computation_offset = computation_offset;
+        self.transition(SessionStatus::Computing)?;
+        self.attempt_started_at = now;
+        self.registry_version = registry_version;
+        self.failure_reason = FAILURE_NONE;
+        Ok(())
+    }
+
+    /// Account size: discriminator + fields, with room for the encrypted
+    /// result and a full retry history.
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 32 // user
+        + 8 // computation_offset
+        + 4 + RESULT_CIPHERTEXT_CAPACITY // result_ciphertext
+        + 16 // result_nonce
+        + 1 // status
+        + 8 // created_at
+        + 8 // attempt_started_at
+        + 1 // failure_reason
+        + 8 // active_computation_offset
+        + 1 // retry_count
+        + 4 + MAX_PSI_RETRIES * RetryRecord::SIZE // retry_history
+        + 8; // registry_version
+}
+
+/// One private "am I registered?" check, created by check_membership and
+/// closed by the caller via close_membership_session. The callback stores
+/// the membership flag encrypted to the caller's x25519 key.
+#[account]
+pub struct MembershipSession {
+    pub bump: u8,
+    /// The user who requested the check
+    pub user: Pubkey,
+    /// Computation offset of the check; also part of the PDA seeds
+    pub computation_offset: u64,
+    /// Enc<Shared, bool> ciphertext (populated by callback)
+    pub result_ciphertext: [u8; 32],
+    /// Result nonce for client-side decryption
+    pub result_nonce: [u8; 16],
+    /// Computing until the callback lands, then Completed or Failed
+    pub status: SessionStatus,
     /// Timestamp of session creation
     pub created_at: i64,
+    /// FAILURE_* code explaining why the check failed (FAILURE_NONE otherwise)
+    pub failure_reason: u8,
+}
+
+impl MembershipSession {
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 32 // user
+        + 8 // computation_offset
+        + CIPHERTEXT_SIZE // result_ciphertext
+        + 16 // result_nonce
+        + 1 // status
+        + 8 // created_at
+        + 1; // failure_reason
+
+    /// Move the session to `next`, as `PsiSession::transition` does.
+    fn transition(&mut self, next: SessionStatus) -> Result<()> {
+        self.status.transition(next)
+    }
+
+    /// Settle the session with the callback's outcome, as
+    /// `PsiSession::settle` does. Returns false for a late outcome, which
+    /// the callback drops.
+    fn settle(&mut self, next: SessionStatus, failure_reason: u8) -> bool {
+        if !self.status.settle(next) {
+            return false;
+        }
+        self.failure_reason = failure_reason;
+        true
+    }
+}
+
+/// FIFO of encrypted registrations awaiting a process_registration_queue
+/// crank, which folds up to REGISTRATION_BATCH_SIZE of them into the
+/// Global Registry in a single MPC computation.
+#[account]
+pub struct RegistrationQueue {
+    pub bump: u8,
+    /// Pending registrations, oldest first
+    pub entries: Vec<PendingRegistration>,
+    /// Entries at the head of the queue covered by the batch in flight
+    pub in_flight: u8,
+}
+
+impl RegistrationQueue {
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 4 + REGISTRATION_QUEUE_CAPACITY * PendingRegistration::SIZE // entries
+        + 1; // in_flight
+}
+
+/// One queued registration: the registrant's encrypted hash and ownership
+/// secret plus the key material the MXE needs to decrypt them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PendingRegistration {
+    /// Wallet that enqueued the registration
+    pub user: Pubkey,
+    /// Encrypted contact hash (Rescue cipher)
+    pub encrypted_hash: [u8; 32],
+    /// Encrypted ownership secret, under the same key and nonce
+    pub encrypted_owner_secret: [u8; 32],
+    /// Registrant's x25519 public key
+    pub pub_key: [u8; 32],
+    /// Encryption nonce
+    pub nonce: u128,
+    /// Hash the entry must match to be inserted
+    pub attested: AttestedHash,
+    /// Timestamp at which the entry was enqueued
+    pub enqueued_at: i64,
+}
+
+impl PendingRegistration {
+    const SIZE: usize = 32 + 32 + 32 + 32 + 16 + AttestedHash::SIZE + 8;
+}
+
+/// The attestation service's own encryption of the identifier hash it
+/// verified, under its ephemeral x25519 key. The registration circuits
+/// insert a registrant's entry only when it decrypts to the same hash.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttestedHash {
+    /// Encrypted contact hash (Rescue cipher)
+    pub encrypted_hash: [u8; 32],
+    /// Attestation service's ephemeral x25519 public key
+    pub pub_key: [u8; 32],
+    /// Encryption nonce
+    pub nonce: u128,
+}
+
+impl AttestedHash {
+    const SIZE: usize = 32 + 32 + 16;
+
+    /// Commitment an attestation over this ciphertext signs.
+    pub fn commitment(&self) -> [u8; 32] {
+        registration_commitment(&self.encrypted_hash, &self.pub_key, self.nonce)
+    }
+}
+
+/// Per-operation prices charged to cover MXE cluster fees. Fees go to the
+/// treasury PDA: lamports by default, or `fee_mint` tokens into the
+/// treasury's token account for that mint once set_fee_mint is called.
+/// Registration stakes are deposited in the same unit, but into the
+/// registrant's own RegistrationEscrow.
+#[account]
+pub struct FeeConfig {
+    pub bump: u8,
+    /// Bump of the treasury PDA ([TREASURY_SEED])
+    pub treasury_bump: u8,
+    /// SPL mint fees are charged in; None charges lamports
+    pub fee_mint: Option<Pubkey>,
+    /// Fee per intersect_contacts
+    pub query_fee: u64,
+    /// Fee per register_user / enqueue_registration
+    pub registration_fee: u64,
+    /// Refundable stake deposited by every registrant (see deposit_stake)
+    pub registration_stake: u64,
+    /// Lifetime lamport fees collected
+    pub lamports_collected: u64,
+    /// Lifetime token fees collected (in the mint active at the time)
+    pub tokens_collected: u64,
+}
+
+impl FeeConfig {
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 1 // treasury_bump
+        + 1 + 32 // fee_mint
+        + 8 // query_fee
+        + 8 // registration_fee
+        + 8 // registration_stake
+        + 8 // lamports_collected
+        + 8; // tokens_collected
+}
+
+/// A wallet's registration stake. Opened by deposit_stake (or
+/// deposit_stake_tokens), it holds a lamport stake in its own balance or a
+/// token stake in its vault ([ESCROW_VAULT_SEED, user]) until the wallet
+/// withdraws it or the authority slashes it. The stake stays locked while
+/// the wallet has a Registration.
+#[account]
+pub struct RegistrationEscrow {
+    pub bump: u8,
+    /// Wallet the stake belongs to
+    pub user: Pubkey,
+    /// Stake held, in lamports or `stake_mint` base units; zero once slashed
+    pub stake_amount: u64,
+    /// Mint of a token stake; None for lamports
+    pub stake_mint: Option<Pubkey>,
+    /// Timestamp of the deposit
+    pub deposited_at: i64,
+}
+
+impl RegistrationEscrow {
+    fn open(
+        &mut self,
+        bump: u8,
+        user: Pubkey,
+        stake_amount: u64,
+        stake_mint: Option<Pubkey>,
+        now: i64,
+    ) {
+        self.bump = bump;
+        self.user = user;
+        self.stake_amount = stake_amount;
+        self.stake_mint = stake_mint;
+        self.deposited_at = now;
+    }
+
+    /// Signer seeds of the escrow PDA, for moving its token stake.
+    fn signer_seeds(&self) -> [&[u8]; 3] {
+        [
+            ESCROW_SEED,
+            self.user.as_ref(),
+            std::slice::from_ref(&self.bump),
+        ]
+    }
+
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 32 // user
+        + 8 // stake_amount
+        + 1 + 32 // stake_mint
+        + 8; // deposited_at
+}
+
+/// A wallet's registration in the Global Registry, at most one per wallet.
+/// Created by register_user or enqueue_registration, it holds the receipt
+/// deregistration needs; it is closed when the registration fails or is
+/// removed, which also unlocks the wallet's stake.
+#[account]
+pub struct Registration {
+    pub bump: u8,
+    /// MXE-encrypted RegistrationReceipt circuit struct, stored by the
+    /// registration callback; empty while Pending
+    pub encrypted_receipt: Vec<u8>,
+    /// Encryption nonce for the receipt
+    pub receipt_nonce: u128,
+    /// Registered wallet
+    pub user: Pubkey,
+    pub status: RegistrationStatus,
+    /// Offset of the latest computation queued for this registration (the
+    /// write in flight, or the last read or write); its callback is the
+    /// only one accepted. 0 while waiting in the registration queue
+    pub computation_offset: u64,
+    /// Timestamp of register_user / enqueue_registration
+    pub created_at: i64,
+    /// Timestamp of the last status change
+    pub updated_at: i64,
+}
+
+impl Registration {
+    fn open(&mut self, bump: u8, user: Pubkey, computation_offset: u64, now: i64) {
+        self.bump = bump;
+        self.encrypted_receipt = vec![];
+        self.receipt_nonce = 0;
+        self.user = user;
+        self.status = RegistrationStatus::Pending;
+        self.computation_offset = computation_offset;
+        self.created_at = now;
+        self.updated_at = now;
+    }
+
+    fn set_status(&mut self, status: RegistrationStatus, now: i64) {
+        self.status = status;
+        self.updated_at = now;
+    }
+
+    /// Store the verified receipt written by `computation_offset`.
+    fn store_receipt(
+        &mut self,
+        ciphertexts: &[[u8; 32]],
+        nonce: u128,
+        computation_offset: u64,
+        now: i64,
+    ) {
+        self.encrypted_receipt = ciphertexts.iter().flat_map(|c| c.to_vec()).collect();
+        self.receipt_nonce = nonce;
+        self.computation_offset = computation_offset;
+        self.set_status(RegistrationStatus::Registered, now);
+    }
+
+    /// Whether the registration waits on a computation whose callback can
+    /// no longer apply: `timeout_secs` have passed since its last status
+    /// change and `write_lock_offset` no longer names its computation.
+    /// Queued registrations (offset 0) are settled by
+    /// process_registration_queue instead.
+    fn is_stranded(&self, write_lock_offset: Option<u64>, now: i64, timeout_secs: i64) -> bool {
+        matches!(
+            self.status,
+            RegistrationStatus::Pending
+                | RegistrationStatus::Deregistering
+                | RegistrationStatus::Updating
+        ) && self.computation_offset != 0
+            && write_lock_offset != Some(self.computation_offset)
+            && now >= self.updated_at.saturating_add(timeout_secs)
+    }
+
+    /// Account size: discriminator + fields, with `encrypted_receipt` sized
+    /// for the circuit's RegistrationReceipt.
+    const SPACE: usize = 8 // discriminator
+        + 1 // bump
+        + 4 + RECEIPT_CIPHERTEXTS * CIPHERTEXT_SIZE // encrypted_receipt
+        + 16 // receipt_nonce
+        + 32 // user
+        + 1 // status
+        + 8 // computation_offset
+        + 8 // created_at
+        + 8; // updated_at
+}
+
+/// Registration lifecycle:
+///
+/// ```text
+/// Pending ──► Registered ──► Deregistering ──► (closed)
+///    │          │  ▲  ▲            │
+///    │          │  │  └────────────┘   (failed callback)
+///    │          ▼  │
+///    │         Updating                (update_registration)
+///    └──► (closed)                     (failed callback)
+/// ```
+///
+/// recover_registration settles Pending (closed), Deregistering and
+/// Updating (both back to Registered) once their callback can no longer
+/// land.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationStatus {
+    /// Registration computation queued or entry enqueued, awaiting its callback
+    Pending,
+    /// Hash stored in the registry; `encrypted_receipt` is set
+    Registered,
+    /// deregister_user queued, awaiting its callback
+    Deregistering,
+    /// update_registration queued, awaiting its callback
+    Updating,
+}
+
+/// PSI session lifecycle:
+///
+/// ```text
+/// Pending ──► Computing ──► Completed
+///                │  ▲
+///                │  └─────── Failed / Expired   (retry_intersect)
+///                ├─► Failed
+///                ├─► Cancelled                  (cancel_session)
+///                └─► Expired                    (expire_session / retry)
+/// ```
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionStatus {
+    /// Account created, computation not yet queued
+    Pending,
+    /// Computation queued, awaiting the MXE callback
+    Computing,
+    /// Callback verified and the encrypted result stored
+    Completed,
+    /// Callback output failed verification (see `failure_reason`)
+    Failed,
+    /// Abandoned by the owner while computing
+    Cancelled,
+    /// No callback arrived within the registry's session timeout
+    Expired,
+}
+
+impl SessionStatus {
+    fn can_transition_to(self, next: SessionStatus) -> bool {
+        use SessionStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Computing)
+                | (Computing, Completed)
+                | (Computing, Failed)
+                | (Computing, Cancelled)
+                | (Computing, Expired)
+                | (Failed, Computing)
+                | (Expired, Computing)
+        )
+    }
+
+    /// States from which no further computation can happen. Failed and
+    /// Expired sessions can still be retried.
+    fn is_terminal(self) -> bool {
+        matches!(self, SessionStatus::Completed | SessionStatus::Cancelled)
+    }
+
+    /// Move to `next`, rejecting transitions the lifecycle does not allow.
+    /// Terminal states report SessionAlreadyComplete.
+    fn transition(&mut self, next: SessionStatus) -> Result<()> {
+        if !self.can_transition_to(next) {
+            msg!(
+                "Blind-Link: illegal session transition {:?} -> {:?}",
+                self,
+                next
+            );
+            return if self.is_terminal() {
+                err!(ErrorCode::SessionAlreadyComplete)
+            } else {
+                err!(ErrorCode::InvalidSessionTransition)
+            };
+        }
+        *self = next;
+        Ok(())
+    }
+
+    /// Move to `next` if the lifecycle allows it; otherwise keep the
+    /// current status and return false.
+    fn settle(&mut self, next: SessionStatus) -> bool {
+        if !self.can_transition_to(next) {
+            msg!(
+                "Blind-Link: session is {:?}; {:?} outcome dropped",
+                self,
+                next
+            );
+            return false;
+        }
+        *self = next;
+        true
+    }
+}
+
+/// A failed PSI attempt recorded when the session is retried.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RetryRecord {
+    /// Computation offset of the failed attempt
+    pub computation_offset: u64,
+    /// FAILURE_* code of the attempt
+    pub failure_reason: u8,
+    /// Timestamp at which the attempt was superseded
+    pub retried_at: i64,
+}
+
+impl RetryRecord {
+    const SIZE: usize = 8 + 1 + 8;
 }
 
 // ── Constants ───────────────────────────────────────────────────────────
 
 const REGISTRY_SEED: &[u8] = b"blind_link_registry";
 const SESSION_SEED: &[u8] = b"psi_session";
+const MEMBERSHIP_SEED: &[u8] = b"membership_session";
+const REGISTRATION_QUEUE_SEED: &[u8] = b"registration_queue";
+const REGISTRY_SHARD_SEED: &[u8] = b"registry_shard";
+const FEE_CONFIG_SEED: &[u8] = b"fee_config";
+const TREASURY_SEED: &[u8] = b"treasury";
+const ESCROW_SEED: &[u8] = b"registration_escrow";
+const ESCROW_VAULT_SEED: &[u8] = b"escrow_vault";
+const REGISTRATION_SEED: &[u8] = b"registration";
+
+/// RegistryShard accounts; must match the circuit's NUM_SHARDS.
+const NUM_REGISTRY_SHARDS: usize = 4;
+
+// Circuit layout mirrored from encrypted-ixs; keep in sync with its
+// constants and structs (checked by layout_constants_match_the_circuit).
+// Every encrypted scalar is one Rescue ciphertext.
+const CIPHERTEXT_SIZE: usize = 32;
+const BUCKETS_PER_SHARD: usize = 4;
+const BUCKET_SIZE: usize = 16;
+/// Ciphertexts in an Enc<Mxe, GlobalRegistry>: total_users, match_epoch,
+/// anomalous_queries and canary_hits.
+const REGISTRY_HEADER_CIPHERTEXTS: usize = 4;
+/// Ciphertexts in an Enc<Mxe, RegistryShard>: each bucket's fingerprints,
+/// owner secrets, hit counters, epoch match counters and canary flags plus
+/// its count.
+const REGISTRY_SHARD_CIPHERTEXTS: usize = BUCKETS_PER_SHARD * (5 * BUCKET_SIZE + 1);
+/// Ciphertexts in an Enc<Mxe, RegistrationReceipt>: hash, owner_secret and
+/// inserted.
+const RECEIPT_CIPHERTEXTS: usize = 3;
+/// Ciphertexts in an Enc<Shared, MatchResult>: one flag per client
+/// contact (MAX_CLIENT_CONTACTS) and the match count.
+const MATCH_RESULT_CIPHERTEXTS: usize = 16 + 1;
+
+/// Computation output one callback transaction carries once its
+/// signature, accounts and instruction header are paid for. Conservative:
+/// a Solana transaction is at most 1232 bytes.
+const CALLBACK_TX_OUTPUT_BYTES: usize = 640;
+/// Output of a computation that writes the registry back: the header and
+/// every shard (ciphertexts and a nonce each), plus the largest other
+/// output a writer returns, intersect_contacts' MatchResult.
+const REGISTRY_WRITE_OUTPUT_BYTES: usize = REGISTRY_HEADER_CIPHERTEXTS * CIPHERTEXT_SIZE + 16 // header
+    + NUM_REGISTRY_SHARDS * (REGISTRY_SHARD_CIPHERTEXTS * CIPHERTEXT_SIZE + 16) // shards
+    + MATCH_RESULT_CIPHERTEXTS * CIPHERTEXT_SIZE + 16; // MatchResult
+/// Callback transactions queued for every registry-writing computation.
+/// Computed at compile time; the build fails if the registry layout grows
+/// past what num_callback_txs can carry.
+const REGISTRY_WRITE_CALLBACK_TXS: u8 = callback_txs(REGISTRY_WRITE_OUTPUT_BYTES);
+
+/// Maximum registrations waiting in the RegistrationQueue.
+const REGISTRATION_QUEUE_CAPACITY: usize = 32;
+/// Entries drained per crank; must match the circuit's REGISTRATION_BATCH_SIZE.
+const REGISTRATION_BATCH_SIZE: usize = 4;
+
+/// Pause flag: blocks every instruction that writes registry entries
+/// (register, enqueue, queue crank, update, deregister, canaries and
+/// bootstrap) while set.
+const PAUSE_REGISTRATION: u8 = 1 << 0;
+/// Pause flag: blocks every registry lookup (intersect_contacts,
+/// retry_intersect, check_membership, read_my_hit_count) while set.
+const PAUSE_DISCOVERY: u8 = 1 << 1;
+const PAUSE_ALL: u8 = PAUSE_REGISTRATION | PAUSE_DISCOVERY;
+
+/// Failure reason codes recorded on PsiSession / RegistryState.
+const FAILURE_NONE: u8 = 0;
+/// The cluster's signed computation output did not verify.
+const FAILURE_VERIFICATION: u8 = 1;
+/// No callback arrived before the session timeout.
+const FAILURE_TIMEOUT: u8 = 2;
+/// The callback's computation no longer held the registry write lock.
+const FAILURE_STALE_WRITE: u8 = 3;
+/// The output does not match the registry layout or does not fit the
+/// allocated accounts (see resize_registry).
+const FAILURE_OUTPUT_SIZE: u8 = 4;
+/// update_registration left the registry unchanged: the old hash was not
+/// present, the new hash's bucket was full or it differed from the
+/// attested hash.
+const FAILURE_UPDATE_REJECTED: u8 = 5;
+/// The registered hash differed from the attested hash; nothing was
+/// inserted.
+const FAILURE_ATTESTATION_MISMATCH: u8 = 6;
+
+/// Bytes reserved for the encrypted MatchResult in PsiSession.
+const RESULT_CIPHERTEXT_CAPACITY: usize = 2048;
+/// Maximum number of retry_intersect calls per PSI session.
+const MAX_PSI_RETRIES: usize = 3;
+
+/// Default time a PSI session may wait on the MXE before it can be expired.
+const DEFAULT_SESSION_TIMEOUT_SECS: i64 = 10 * 60;
+/// Default length of the epoch over which per-entry matches are capped.
+const DEFAULT_MATCH_EPOCH_SECS: i64 = 24 * 60 * 60;
+
+/// Domain separator prefixed to every registration attestation message.
+pub const ATTESTATION_DOMAIN: &[u8] = b"blind-link:attest:v1";
+
+// ── Helpers ─────────────────────────────────────────────────────────────
+
+/// Callback transactions needed to deliver `output_bytes` of computation
+/// output, at CALLBACK_TX_OUTPUT_BYTES each. Panics (at compile time when
+/// used in a const) if that exceeds the u8 num_callback_txs.
+const fn callback_txs(output_bytes: usize) -> u8 {
+    let txs = output_bytes.div_ceil(CALLBACK_TX_OUTPUT_BYTES);
+    assert!(
+        txs <= u8::MAX as usize,
+        "computation output does not fit in num_callback_txs"
+    );
+    txs as u8
+}
+
+/// Commitment to one MXE-encrypted blob (the registry header or a shard):
+/// SHA-256(encrypted_data || nonce_le).
+pub fn registry_state_hash(encrypted_data: &[u8], nonce: u128) -> [u8; 32] {
+    hashv(&[encrypted_data, &nonce.to_le_bytes()]).to_bytes()
+}
+
+/// Commitment to a full registry snapshot: SHA-256 over the header's
+/// commitment followed by each shard's, in shard order.
+pub fn registry_snapshot_hash(header_hash: &[u8; 32], shard_hashes: &[[u8; 32]]) -> [u8; 32] {
+    let mut parts = vec![header_hash.as_slice()];
+    parts.extend(shard_hashes.iter().map(|h| h.as_slice()));
+    hashv(&parts).to_bytes()
+}
+
+/// One link of the registry transparency log:
+/// SHA-256(prev_head || state_hash || computation_offset_le || slot_le).
+pub fn transition_link(
+    prev_head: &[u8; 32],
+    state_hash: &[u8; 32],
+    computation_offset: u64,
+    slot: u64,
+) -> [u8; 32] {
+    hashv(&[
+        prev_head,
+        state_hash,
+        &computation_offset.to_le_bytes(),
+        &slot.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Commitment an attestation signs in place of the identifier hash:
+/// SHA-256(encrypted_hash || pub_key || nonce_le) over the attestation
+/// service's own encryption of the hash it verified (see AttestedHash).
+pub fn registration_commitment(
+    encrypted_hash: &[u8; 32],
+    pub_key: &[u8; 32],
+    nonce: u128,
+) -> [u8; 32] {
+    hashv(&[encrypted_hash, pub_key, &nonce.to_le_bytes()]).to_bytes()
+}
+
+/// Message the attestation service signs once the registrant has proven
+/// ownership of the identifier:
+/// ATTESTATION_DOMAIN || wallet || commitment || expiry_le.
+pub fn attestation_message(wallet: &Pubkey, commitment: &[u8; 32], expiry: i64) -> Vec<u8> {
+    [
+        ATTESTATION_DOMAIN,
+        wallet.as_ref(),
+        commitment,
+        &expiry.to_le_bytes(),
+    ]
+    .concat()
+}
+
+/// Whether `ix` is an Ed25519 program instruction verifying exactly one
+/// signature by `signer` over `message`, with all offsets pointing into its
+/// own data (the layout web3.js `Ed25519Program` produces).
+fn ed25519_instruction_verifies(ix: &Instruction, signer: &Pubkey, message: &[u8]) -> bool {
+    let data = &ix.data;
+    if ix.program_id != ed25519_program::ID || data.len() < 16 || data[0] != 1 {
+        return false;
+    }
+    let read = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    // Signature, public key and message must live in this instruction
+    if [read(4), read(8), read(14)]
+        .iter()
+        .any(|&index| index != u16::MAX)
+    {
+        return false;
+    }
+    let (key_offset, message_offset, message_len) =
+        (read(6) as usize, read(10) as usize, read(12) as usize);
+    data.get(key_offset..key_offset + 32) == Some(signer.as_ref())
+        && data.get(message_offset..message_offset + message_len) == Some(message)
+}
+
+/// Enforce the registry's attestation requirement, if any, for a
+/// registration by `wallet`, returning the hash the circuit checks the
+/// entry against. The Ed25519 program instruction carrying the signature
+/// over `attested` must immediately precede the current instruction; the
+/// runtime has already verified the signature itself. Without an
+/// attestation key the registrant's own hash ciphertext `own` stands in,
+/// so the check always passes.
+fn require_attestation(
+    registry: &RegistryState,
+    instructions_sysvar: &AccountInfo,
+    wallet: &Pubkey,
+    attested: Option<AttestedHash>,
+    own: AttestedHash,
+    expiry: i64,
+) -> Result<AttestedHash> {
+    let Some(attester) = registry.attestation_key else {
+        return Ok(own);
+    };
+    let attested = attested.ok_or(error!(ErrorCode::AttestationMissing))?;
+    require!(
+        Clock::get()?.unix_timestamp <= expiry,
+        ErrorCode::AttestationExpired
+    );
+
+    let current = load_current_index_checked(instructions_sysvar)?;
+    require!(current > 0, ErrorCode::AttestationMissing);
+    let ix = load_instruction_at_checked(current as usize - 1, instructions_sysvar)?;
+    require!(
+        ed25519_instruction_verifies(
+            &ix,
+            &attester,
+            &attestation_message(wallet, &attested.commitment(), expiry)
+        ),
+        ErrorCode::InvalidAttestation
+    );
+    Ok(attested)
+}
+
+/// Byte offset of the MXE ciphertexts in a RegistryState, RegistryShard or
+/// Registration account: all begin with the discriminator, `bump`,
+/// and the Borsh u32 length prefix of their ciphertext Vec.
+pub const ENCRYPTED_DATA_OFFSET: usize = 8 // discriminator
+    + 1 // bump
+    + 4; // encrypted_data length prefix
+
+/// `(offset, length)` of the ciphertext region of a registry account whose
+/// `encrypted_data` is `encrypted_data`, as passed to `ArgBuilder::account`.
+pub fn encrypted_data_region(encrypted_data: &[u8]) -> (u32, u32) {
+    (ENCRYPTED_DATA_OFFSET as u32, encrypted_data.len() as u32)
+}
+
+/// Append an Enc<Mxe, _> argument stored in a registry account: its MXE
+/// nonce, then the account's ciphertext region.
+fn registry_account_arg(
+    arg_builder: ArgBuilder,
+    key: Pubkey,
+    encrypted_data: &[u8],
+    nonce: u128,
+) -> ArgBuilder {
+    let (offset, len) = encrypted_data_region(encrypted_data);
+    arg_builder.plaintext_u128(nonce).account(key, offset, len)
+}
+
+/// Append an `Enc<Shared, ContactHash>` argument for `attested`.
+fn attested_hash_arg(arg_builder: ArgBuilder, attested: &AttestedHash) -> ArgBuilder {
+    arg_builder
+        .x25519_pubkey(attested.pub_key)
+        .plaintext_u128(attested.nonce)
+        .encrypted_u128(attested.encrypted_hash)
+}
+
+/// `candidate` if it is the offset of `computation_account`, otherwise 0.
+/// Lets callbacks that only see the computation account name its offset in
+/// failure events.
+fn computation_offset_of(
+    candidate: Option<u64>,
+    computation_account: Pubkey,
+    mxe_account: &MXEAccount,
+) -> Result<u64> {
+    match candidate {
+        Some(offset)
+            if computation_account
+                == derive_comp_pda!(offset, mxe_account, ErrorCode::ClusterNotSet) =>
+        {
+            Ok(offset)
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Deserialize the registry shards passed as remaining accounts, checking
+/// they are exactly the NUM_REGISTRY_SHARDS shard PDAs, in index order.
+fn load_registry_shards(accounts: &[AccountInfo]) -> Result<Vec<RegistryShard>> {
+    require!(
+        accounts.len() == NUM_REGISTRY_SHARDS,
+        ErrorCode::InvalidRegistryShards
+    );
+    accounts
+        .iter()
+        .enumerate()
+        .map(|(index, info)| {
+            require_keys_eq!(*info.owner, crate::ID, ErrorCode::InvalidRegistryShards);
+            let shard = RegistryShard::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+            let expected = Pubkey::create_program_address(
+                &[REGISTRY_SHARD_SEED, &[index as u8], &[shard.bump]],
+                &crate::ID,
+            )
+            .map_err(|_| error!(ErrorCode::InvalidRegistryShards))?;
+            require_keys_eq!(*info.key, expected, ErrorCode::InvalidRegistryShards);
+            Ok(shard)
+        })
+        .collect()
+}
+
+/// Append one Enc<Mxe, RegistryShard> argument per shard, in index order.
+fn registry_shard_args(
+    mut arg_builder: ArgBuilder,
+    accounts: &[AccountInfo],
+) -> Result<ArgBuilder> {
+    for (info, shard) in accounts.iter().zip(load_registry_shards(accounts)?) {
+        arg_builder =
+            registry_account_arg(arg_builder, *info.key, &shard.encrypted_data, shard.nonce);
+    }
+    Ok(arg_builder)
+}
+
+/// Writable callback accounts for every shard, in index order. Callbacks
+/// receive them as remaining accounts.
+fn registry_shard_callback_accounts(accounts: &[AccountInfo]) -> Vec<CallbackAccount> {
+    accounts
+        .iter()
+        .map(|info| CallbackAccount {
+            pubkey: *info.key,
+            is_writable: true,
+        })
+        .collect()
+}
+
+/// Write verified MXE output back into each shard, in index order, and
+/// return the shards' commitments for `RegistryState::commit_state`.
+fn store_registry_shards(
+    accounts: &[AccountInfo],
+    outputs: &[(&[[u8; 32]], u128)],
+) -> Result<Vec<[u8; 32]>> {
+    let shards = load_registry_shards(accounts)?;
+    let mut hashes = Vec::with_capacity(NUM_REGISTRY_SHARDS);
+    for ((info, mut shard), (ciphertexts, nonce)) in accounts.iter().zip(shards).zip(outputs) {
+        require!(info.is_writable, ErrorCode::InvalidRegistryShards);
+        shard.encrypted_data = ciphertexts.iter().flat_map(|c| c.to_vec()).collect();
+        shard.nonce = *nonce;
+        hashes.push(registry_state_hash(&shard.encrypted_data, shard.nonce));
+        shard.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    }
+    Ok(hashes)
+}
+
+/// Store a verified registry-writing output: the header ciphertexts and
+/// nonce, then each shard's, in index order. Returns Ok(false) and writes
+/// nothing when the output does not match the circuit layout or an account
+/// is too small to hold it, so the callback can record the failure rather
+/// than abort on serialization.
+fn store_registry_output(
+    registry: &mut Account<RegistryState>,
+    shard_accounts: &[AccountInfo],
+    header: (&[[u8; 32]], u128),
+    shards: &[(&[[u8; 32]], u128)],
+    computation_offset: u64,
+) -> Result<bool> {
+    let header_fits = header.0.len() == REGISTRY_HEADER_CIPHERTEXTS
+        && registry.to_account_info().data_len() >= RegistryState::SPACE;
+    let shards_fit = shards.len() == shard_accounts.len()
+        && shard_accounts
+            .iter()
+            .zip(shards)
+            .all(|(info, (ciphertexts, _))| {
+                ciphertexts.len() == REGISTRY_SHARD_CIPHERTEXTS
+                    && info.data_len() >= RegistryShard::SPACE
+            });
+    if !(header_fits && shards_fit) {
+        msg!(
+            "Blind-Link: Registry output does not fit (header: {} ciphertexts, {} bytes allocated)",
+            header.0.len(),
+            registry.to_account_info().data_len()
+        );
+        return Ok(false);
+    }
+
+    let shard_hashes = store_registry_shards(shard_accounts, shards)?;
+    registry.commit_state(header.0, header.1, &shard_hashes, computation_offset)?;
+    Ok(true)
+}
+
+/// Deserialize a Registration passed as a remaining account, checking it
+/// is `user`'s registration PDA.
+fn load_registration(info: &AccountInfo, user: &Pubkey) -> Result<Registration> {
+    require_keys_eq!(*info.owner, crate::ID, ErrorCode::InvalidRegistration);
+    let registration = Registration::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    let expected = Pubkey::create_program_address(
+        &[REGISTRATION_SEED, user.as_ref(), &[registration.bump]],
+        &crate::ID,
+    )
+    .map_err(|_| error!(ErrorCode::InvalidRegistration))?;
+    require_keys_eq!(*info.key, expected, ErrorCode::InvalidRegistration);
+    Ok(registration)
+}
+
+/// Close a program-owned account passed as a remaining account, where
+/// Anchor's `close` constraint is unavailable, sending its rent to
+/// `destination`.
+fn close_remaining_account<'info>(
+    info: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    let rent = info.lamports();
+    **destination.try_borrow_mut_lamports()? += rent;
+    **info.try_borrow_mut_lamports()? = 0;
+    info.assign(&system_program::ID);
+    info.resize(0)?;
+    Ok(())
+}
+
+/// Next size on the way to `required` bytes: the runtime grows an account
+/// by at most MAX_PERMITTED_DATA_INCREASE per instruction.
+fn resize_step(current: usize, required: usize) -> usize {
+    required.min(current + MAX_PERMITTED_DATA_INCREASE)
+}
+
+/// Build the intersect_contacts circuit arguments:
+///   Arg 1 (Enc<Shared, ClientContacts>): client's encrypted contacts
+///   Arg 2 (u64): current match epoch
+///   Arg 3 (u64): per-entry match cap for the epoch
+///   Arg 4 (u64): per-query match cap
+///   Arg 5 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
+///   Arg 6-9 (Enc<Mxe, RegistryShard>): read from the registry shards
+fn intersect_contacts_args(
+    registry: &Account<RegistryState>,
+    shards: &[AccountInfo],
+    encrypted_hashes: &[[u8; 32]],
+    encrypted_count: [u8; 32],
+    pub_key: [u8; 32],
+    nonce: u128,
+    now: i64,
+) -> Result<ArgBuilder> {
+    let mut arg_builder = ArgBuilder::new()
+        .x25519_pubkey(pub_key)
+        .plaintext_u128(nonce);
+
+    // Append each encrypted contact hash
+    for hash_ct in encrypted_hashes.iter() {
+        arg_builder = arg_builder.encrypted_u128(*hash_ct);
+    }
+    // Append encrypted count
+    arg_builder = arg_builder.encrypted_u64(encrypted_count);
+
+    let (epoch, match_cap) = registry.match_limit(now);
+    arg_builder = arg_builder
+        .plaintext_u64(epoch)
+        .plaintext_u64(match_cap)
+        .plaintext_u64(registry.query_match_cap());
+
+    registry_shard_args(
+        registry_account_arg(
+            arg_builder,
+            registry.key(),
+            &registry.encrypted_data,
+            registry.nonce,
+        ),
+        shards,
+    )
+}
+
+/// Callback accounts for intersect_contacts: the session, the registry
+/// header, then the shards it writes back.
+fn intersect_callback_accounts(
+    psi_session: Pubkey,
+    registry_state: Pubkey,
+    shards: &[AccountInfo],
+) -> Vec<CallbackAccount> {
+    let mut accounts = vec![
+        CallbackAccount {
+            pubkey: psi_session,
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: registry_state,
+            is_writable: true,
+        },
+    ];
+    accounts.extend(registry_shard_callback_accounts(shards));
+    accounts
+}
 
 // ── Program ─────────────────────────────────────────────────────────────
 
@@ -72,13 +1312,54 @@ pub mod blind_link {
         registry.authority = ctx.accounts.authority.key();
         registry.computation_count = 0;
         registry.nonce = 0;
+        registry.pending_authority = None;
+        registry.paused = 0;
+        registry.bootstrapped = false;
+        registry.reset_armed = false;
+        registry.session_timeout_secs = DEFAULT_SESSION_TIMEOUT_SECS;
+        registry.failed_computations = 0;
+        registry.last_failure_reason = FAILURE_NONE;
+        registry.write_lock_offset = None;
+        registry.write_locked_at = 0;
+        registry.version = 0;
         // Encrypted data initialized empty; first register_user call populates it
         registry.encrypted_data = vec![0u8; 0];
+        registry.state_hash = registry_state_hash(&registry.encrypted_data, registry.nonce);
+        registry.transition_head = [0u8; 32];
+        registry.attestation_key = None;
+        registry.match_cap_per_epoch = 0;
+        registry.match_epoch_secs = DEFAULT_MATCH_EPOCH_SECS;
+        registry.max_matches_per_query = 0;
+        registry.reclaimed_lock_offset = None;
+        registry.reveal_offset = None;
 
         msg!("Blind-Link: Global Registry initialized");
         Ok(())
     }
 
+    /// Create registry shard `index`. The authority creates every shard,
+    /// 0..NUM_REGISTRY_SHARDS, and grows it to full size with
+    /// resize_registry_shard before bootstrapping the registry.
+    pub fn initialize_registry_shard(
+        ctx: Context<InitializeRegistryShard>,
+        index: u8,
+    ) -> Result<()> {
+        require!(
+            (index as usize) < NUM_REGISTRY_SHARDS,
+            ErrorCode::InvalidRegistryShards
+        );
+
+        let shard = &mut ctx.accounts.registry_shard;
+        shard.bump = ctx.bumps.registry_shard;
+        shard.index = index;
+        shard.nonce = 0;
+        // Populated by the init_registry callback
+        shard.encrypted_data = vec![];
+
+        msg!("Blind-Link: Registry shard {} initialized", index);
+        Ok(())
+    }
+
     // ── 2. Computation Definition Initializers ──────────────────────
 
     /// Initialize the computation definition for intersect_contacts.
@@ -107,6 +1388,31 @@ pub mod blind_link {
         Ok(())
     }
 
+    /// Initialize the computation definition for reveal_anomaly_count.
+    pub fn init_reveal_anomaly_count_comp_def(
+        ctx: Context<InitRevealAnomalyCountCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: reveal_anomaly_count comp_def registered");
+        Ok(())
+    }
+
+    /// Initialize the computation definition for insert_canary.
+    pub fn init_insert_canary_comp_def(ctx: Context<InitInsertCanaryCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: insert_canary comp_def registered");
+        Ok(())
+    }
+
+    /// Initialize the computation definition for reveal_canary_hits.
+    pub fn init_reveal_canary_hits_comp_def(
+        ctx: Context<InitRevealCanaryHitsCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: reveal_canary_hits comp_def registered");
+        Ok(())
+    }
+
     /// Initialize the computation definition for init_registry.
     pub fn init_init_registry_comp_def(ctx: Context<InitInitRegistryCompDef>) -> Result<()> {
         init_comp_def(ctx.accounts, None, None)?;
@@ -114,7 +1420,46 @@ pub mod blind_link {
         Ok(())
     }
 
-    // ── 3. Queue PSI Computation ────────────────────────────────────
+    /// Initialize the computation definition for process_registration_queue.
+    pub fn init_process_registration_queue_comp_def(
+        ctx: Context<InitProcessRegistrationQueueCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: process_registration_queue comp_def registered");
+        Ok(())
+    }
+
+    /// Initialize the computation definition for deregister_user.
+    pub fn init_deregister_user_comp_def(ctx: Context<InitDeregisterUserCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: deregister_user comp_def registered");
+        Ok(())
+    }
+
+    /// Initialize the computation definition for update_registration.
+    pub fn init_update_registration_comp_def(
+        ctx: Context<InitUpdateRegistrationCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: update_registration comp_def registered");
+        Ok(())
+    }
+
+    /// Initialize the computation definition for check_membership.
+    pub fn init_check_membership_comp_def(ctx: Context<InitCheckMembershipCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: check_membership comp_def registered");
+        Ok(())
+    }
+
+    /// Initialize the computation definition for read_my_hit_count.
+    pub fn init_read_my_hit_count_comp_def(ctx: Context<InitReadMyHitCountCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        msg!("Blind-Link: read_my_hit_count comp_def registered");
+        Ok(())
+    }
+
+    // ── 3. Queue PSI Computation ────────────────────────────────────
 
     /// Submit encrypted contact hashes for private intersection.
     ///
@@ -125,6 +1470,20 @@ pub mod blind_link {
     ///   4. MPC nodes execute intersect_contacts circuit
     ///   5. Result returned via intersect_contacts_callback
     ///
+    /// The NUM_REGISTRY_SHARDS registry shards are passed as remaining
+    /// accounts, in index order. The circuit bumps the hit counter of every
+    /// matched entry, so the query takes the registry write lock like
+    /// register_user: discovery is serialized with every other query and
+    /// registry write, and a concurrent one fails with RegistryWriteLocked
+    /// and should be resubmitted. Each hold of the lock costs the caller
+    /// the query fee, which must be positive (see set_fees), and lasts at
+    /// most the session timeout, after which expire_session or the next
+    /// writer reclaims it. Entries
+    /// that already reported `match_cap_per_epoch` matches this epoch read
+    /// as non-matches (see set_match_rate_limit), and a query matching more
+    /// than `max_matches_per_query` contacts reads as no matches at all (see
+    /// set_query_match_cap).
+    ///
     /// # Arguments
     /// * `computation_offset` - Unique ID for this computation
     /// * `encrypted_hashes` - Client's encrypted contact hashes (Rescue cipher)
@@ -139,41 +1498,52 @@ pub mod blind_link {
         pub_key: [u8; 32],
         nonce: u128,
     ) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_DISCOVERY == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            ctx.accounts.registry_state.bootstrapped,
+            ErrorCode::RegistryNotBootstrapped
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts
+            .registry_state
+            .acquire_write_lock(computation_offset, now)?;
+
         // Initialize session tracking account
         let session = &mut ctx.accounts.psi_session;
         session.bump = ctx.bumps.psi_session;
         session.user = ctx.accounts.user.key();
         session.computation_offset = computation_offset;
-        session.status = 1; // computing
-        session.created_at = Clock::get()?.unix_timestamp;
+        session.status = SessionStatus::Pending;
+        session.transition(SessionStatus::Computing)?;
+        session.created_at = now;
+        session.attempt_started_at = now;
         session.result_ciphertext = vec![];
         session.result_nonce = [0u8; 16];
+        session.failure_reason = FAILURE_NONE;
+        session.active_computation_offset = computation_offset;
+        session.retry_count = 0;
+        session.retry_history = vec![];
+        session.registry_version = ctx.accounts.registry_state.version;
 
-        // Build computation arguments:
-        // Arg 1 (Enc<Shared, ClientContacts>): client's encrypted contacts
-        let mut arg_builder = ArgBuilder::new()
-            .x25519_pubkey(pub_key)
-            .plaintext_u128(nonce);
-
-        // Append each encrypted contact hash
-        for hash_ct in encrypted_hashes.iter() {
-            arg_builder = arg_builder.encrypted_u128(*hash_ct);
-        }
-        // Append encrypted count
-        arg_builder = arg_builder.encrypted_u64(encrypted_count);
-
-        // Arg 2 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
-        let registry_key = ctx.accounts.registry_state.key();
-        let registry_data_offset = 8 + 1; // discriminator + bump
-        let registry_data_len = ctx.accounts.registry_state.encrypted_data.len();
+        let query_fee = ctx.accounts.fees.fee_config.query_fee;
+        ctx.accounts
+            .fees
+            .collect(&ctx.accounts.user, &ctx.accounts.system_program, query_fee)?;
 
-        let args = arg_builder
-            .account(
-                registry_key,
-                registry_data_offset as u32,
-                registry_data_len as u32,
-            )
-            .build();
+        let args = intersect_contacts_args(
+            &ctx.accounts.registry_state,
+            ctx.remaining_accounts,
+            &encrypted_hashes,
+            encrypted_count,
+            pub_key,
+            nonce,
+            now,
+        )?
+        .build();
 
         // Initialize sign PDA bump for CPI signing
         ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
@@ -186,12 +1556,13 @@ pub mod blind_link {
             vec![IntersectContactsCallback::callback_ix(
                 computation_offset,
                 &ctx.accounts.mxe_account,
-                &[CallbackAccount {
-                    pubkey: ctx.accounts.psi_session.key(),
-                    is_writable: true,
-                }],
+                &intersect_callback_accounts(
+                    ctx.accounts.psi_session.key(),
+                    ctx.accounts.registry_state.key(),
+                    ctx.remaining_accounts,
+                ),
             )?],
-            1, // num_transactions
+            REGISTRY_WRITE_CALLBACK_TXS,
             0, // priority_fee
         )?;
 
@@ -210,13 +1581,36 @@ pub mod blind_link {
     // ── 4. PSI Callback ─────────────────────────────────────────────
 
     /// Callback invoked by Arcium after MXE completes the intersection.
-    /// Verifies the cluster's execution proof and stores the encrypted
-    /// result for client retrieval.
+    /// Verifies the cluster's execution proof, writes back the registry
+    /// (with updated hit counters) and stores the encrypted result for
+    /// client retrieval. Output from a computation that lost the write lock
+    /// is discarded. Failures are recorded on the session and the callback
+    /// still succeeds, so the failure persists.
     #[arcium_callback(encrypted_ix = "intersect_contacts")]
     pub fn intersect_contacts_callback(
         ctx: Context<IntersectContactsCallback>,
         output: SignedComputationOutputs<IntersectContactsOutput>,
     ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        if !registry.holds_write_lock(
+            ctx.accounts.computation_account.key(),
+            &ctx.accounts.mxe_account,
+        )? {
+            msg!("Blind-Link: PSI computed against a stale registry; discarded");
+            registry.record_failure(FAILURE_STALE_WRITE);
+            let session = &mut ctx.accounts.psi_session;
+            session.settle(SessionStatus::Failed, FAILURE_STALE_WRITE);
+
+            emit!(PsiFailedEvent {
+                user: session.user,
+                computation_offset: session.computation_offset,
+                active_computation_offset: session.active_computation_offset,
+                reason: FAILURE_STALE_WRITE,
+            });
+            return Ok(());
+        }
+        let computation_offset = registry.release_write_lock();
+
         let verified = match output.verify_output(
             &ctx.accounts.cluster_account,
             &ctx.accounts.computation_account,
@@ -224,60 +1618,257 @@ pub mod blind_link {
             Ok(out) => out,
             Err(e) => {
                 msg!("Blind-Link: PSI verification failed: {}", e);
-                // Mark session as failed
+                ctx.accounts
+                    .registry_state
+                    .record_failure(FAILURE_VERIFICATION);
+                // Mark session as failed; returning Ok keeps the write
                 let session = &mut ctx.accounts.psi_session;
-                session.status = 3; // failed
-                return Err(ErrorCode::VerificationFailed.into());
+                session.settle(SessionStatus::Failed, FAILURE_VERIFICATION);
+
+                emit!(PsiFailedEvent {
+                    user: session.user,
+                    computation_offset: session.computation_offset,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
             }
         };
 
+        // Write back the registry header and shards (hit counters)
+        let outputs = &verified.field_0;
+        let registry = &mut ctx.accounts.registry_state;
+        if !store_registry_output(
+            registry,
+            ctx.remaining_accounts,
+            (&outputs.field_1.ciphertexts[..], outputs.field_1.nonce),
+            &[
+                (&outputs.field_2.ciphertexts[..], outputs.field_2.nonce),
+                (&outputs.field_3.ciphertexts[..], outputs.field_3.nonce),
+                (&outputs.field_4.ciphertexts[..], outputs.field_4.nonce),
+                (&outputs.field_5.ciphertexts[..], outputs.field_5.nonce),
+            ],
+            computation_offset,
+        )? {
+            registry.record_failure(FAILURE_OUTPUT_SIZE);
+            let session = &mut ctx.accounts.psi_session;
+            session.settle(SessionStatus::Failed, FAILURE_OUTPUT_SIZE);
+
+            emit!(PsiFailedEvent {
+                user: session.user,
+                computation_offset: session.computation_offset,
+                active_computation_offset: session.active_computation_offset,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        }
+
         // Store encrypted result in session account for client retrieval
+        let result = &outputs.field_0;
         let session = &mut ctx.accounts.psi_session;
-        session.result_ciphertext = verified
-            .field_0
-            .ciphertexts
-            .iter()
-            .flat_map(|c| c.to_vec())
-            .collect();
-        session.result_nonce = verified.field_0.nonce.to_le_bytes();
-        session.status = 2; // completed
+        if !session.settle(SessionStatus::Completed, FAILURE_NONE) {
+            return Ok(());
+        }
+        session.result_ciphertext = result.ciphertexts.iter().flat_map(|c| c.to_vec()).collect();
+        session.result_nonce = result.nonce.to_le_bytes();
 
         emit!(PsiCompleteEvent {
             user: session.user,
             computation_offset: session.computation_offset,
-            result_ciphertexts: verified.field_0.ciphertexts.to_vec(),
-            result_nonce: verified.field_0.nonce.to_le_bytes(),
+            result_ciphertexts: result.ciphertexts.to_vec(),
+            result_nonce: result.nonce.to_le_bytes(),
+            registry_version: session.registry_version,
         });
 
         msg!("Blind-Link: PSI computation completed successfully");
         Ok(())
     }
 
+    /// Re-queue the intersection for a session whose computation failed or
+    /// timed out, reusing the session account. The client must submit fresh
+    /// ciphertexts (new x25519 key and nonce) and a new computation offset;
+    /// the superseded attempt is appended to the session's retry history.
+    /// Takes the registry write lock and charges the query fee like
+    /// intersect_contacts, so retries are serialized and priced the same.
+    pub fn retry_intersect(
+        ctx: Context<RetryIntersect>,
+        computation_offset: u64,
+        encrypted_hashes: Vec<[u8; 32]>,
+        encrypted_count: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_DISCOVERY == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            ctx.accounts.registry_state.bootstrapped,
+            ErrorCode::RegistryNotBootstrapped
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts
+            .registry_state
+            .acquire_write_lock(computation_offset, now)?;
+        let registry = &ctx.accounts.registry_state;
+        ctx.accounts.psi_session.begin_retry(
+            computation_offset,
+            now,
+            registry.session_timeout_secs,
+            registry.version,
+        )?;
+
+        let query_fee = ctx.accounts.fees.fee_config.query_fee;
+        ctx.accounts
+            .fees
+            .collect(&ctx.accounts.user, &ctx.accounts.system_program, query_fee)?;
+
+        let args = intersect_contacts_args(
+            &ctx.accounts.registry_state,
+            ctx.remaining_accounts,
+            &encrypted_hashes,
+            encrypted_count,
+            pub_key,
+            nonce,
+            now,
+        )?
+        .build();
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![IntersectContactsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &intersect_callback_accounts(
+                    ctx.accounts.psi_session.key(),
+                    ctx.accounts.registry_state.key(),
+                    ctx.remaining_accounts,
+                ),
+            )?],
+            REGISTRY_WRITE_CALLBACK_TXS,
+            0,
+        )?;
+
+        let registry = &mut ctx.accounts.registry_state;
+        registry.computation_count += 1;
+
+        msg!(
+            "Blind-Link: PSI computation re-queued (session offset: {}, attempt: {})",
+            ctx.accounts.psi_session.computation_offset,
+            ctx.accounts.psi_session.retry_count + 1
+        );
+        Ok(())
+    }
+
     // ── 5. Register User ────────────────────────────────────────────
 
     /// Add a new user's contact hash to the Global Registry.
-    /// The hash is encrypted client-side and inserted into the MXE state.
+    /// The hash is encrypted client-side and inserted into the MXE state,
+    /// together with an ownership secret (same key and nonce) that later
+    /// proves the entry is the caller's (see read_my_hit_count).
+    ///
+    /// Takes the registry write lock until the callback lands; a concurrent
+    /// registration fails with RegistryWriteLocked and should be resubmitted.
+    /// The registry shards are passed as remaining accounts, in index order.
+    /// Charges the configured registration fee and requires the caller's
+    /// staked RegistrationEscrow. Creates the wallet's Registration, so a
+    /// wallet with a live registration cannot register again.
+    ///
+    /// When the registry has an attestation key, the transaction must carry
+    /// that key's Ed25519 signature over `attestation_message` for
+    /// `attested` in the instruction just before this one, valid until
+    /// `attestation_expiry`; the circuit inserts the entry only if its hash
+    /// matches the attested one. Otherwise `attested` and
+    /// `attestation_expiry` are ignored.
     pub fn register_user(
         ctx: Context<RegisterUser>,
         computation_offset: u64,
         encrypted_hash: [u8; 32],
+        encrypted_owner_secret: [u8; 32],
         pub_key: [u8; 32],
         nonce: u128,
+        attested: Option<AttestedHash>,
+        attestation_expiry: i64,
     ) -> Result<()> {
-        let registry_key = ctx.accounts.registry_state.key();
-        let registry_data_offset = 8 + 1;
-        let registry_data_len = ctx.accounts.registry_state.encrypted_data.len();
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_REGISTRATION == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            ctx.accounts.registry_state.bootstrapped,
+            ErrorCode::RegistryNotBootstrapped
+        );
+        let attested = require_attestation(
+            &ctx.accounts.registry_state,
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.user.key(),
+            attested,
+            AttestedHash {
+                encrypted_hash,
+                pub_key,
+                nonce,
+            },
+            attestation_expiry,
+        )?;
+        ctx.accounts
+            .registry_state
+            .acquire_write_lock(computation_offset, Clock::get()?.unix_timestamp)?;
+
+        let registration_fee = ctx.accounts.fees.fee_config.registration_fee;
+        ctx.accounts.fees.collect(
+            &ctx.accounts.user,
+            &ctx.accounts.system_program,
+            registration_fee,
+        )?;
+        let user = ctx.accounts.user.key();
+        ctx.accounts.registration.open(
+            ctx.bumps.registration,
+            user,
+            computation_offset,
+            Clock::get()?.unix_timestamp,
+        );
 
-        let args = ArgBuilder::new()
-            .x25519_pubkey(pub_key)
-            .plaintext_u128(nonce)
-            .encrypted_u128(encrypted_hash)
-            .account(
+        let registry = &ctx.accounts.registry_state;
+        let registry_key = registry.key();
+
+        let args = registry_shard_args(
+            registry_account_arg(
+                attested_hash_arg(
+                    ArgBuilder::new()
+                        .x25519_pubkey(pub_key)
+                        .plaintext_u128(nonce)
+                        .encrypted_u128(encrypted_hash)
+                        .encrypted_u128(encrypted_owner_secret),
+                    &attested,
+                ),
                 registry_key,
-                registry_data_offset as u32,
-                registry_data_len as u32,
-            )
-            .build();
+                &registry.encrypted_data,
+                registry.nonce,
+            ),
+            ctx.remaining_accounts,
+        )?
+        .build();
+
+        let mut callback_accounts = vec![
+            CallbackAccount {
+                pubkey: registry_key,
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.registration.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: user,
+                is_writable: true,
+            },
+        ];
+        callback_accounts.extend(registry_shard_callback_accounts(ctx.remaining_accounts));
 
         // Initialize sign PDA bump for CPI signing
         ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
@@ -289,12 +1880,9 @@ pub mod blind_link {
             vec![RegisterUserCallback::callback_ix(
                 computation_offset,
                 &ctx.accounts.mxe_account,
-                &[CallbackAccount {
-                    pubkey: ctx.accounts.registry_state.key(),
-                    is_writable: true,
-                }],
+                &callback_accounts,
             )?],
-            1,
+            REGISTRY_WRITE_CALLBACK_TXS,
             0,
         )?;
 
@@ -302,12 +1890,41 @@ pub mod blind_link {
         Ok(())
     }
 
-    /// Callback for register_user: updates the on-chain encrypted registry state.
+    /// Callback for register_user: updates the on-chain encrypted registry state
+    /// and stores the receipt in the wallet's Registration. Only the
+    /// registration's latest computation is accepted, so a callback that
+    /// outlived recover_registration cannot touch a newer registration.
+    /// Output from a computation that lost the write lock is discarded; on
+    /// any failure, including an entry that did not match its attested
+    /// hash, the Registration is closed so the wallet can register again.
     #[arcium_callback(encrypted_ix = "register_user")]
     pub fn register_user_callback(
         ctx: Context<RegisterUserCallback>,
         output: SignedComputationOutputs<RegisterUserOutput>,
     ) -> Result<()> {
+        let user = ctx.accounts.user.key();
+        let queued_offset = ctx.accounts.registration.computation_offset;
+        let registry = &mut ctx.accounts.registry_state;
+        if !registry.holds_write_lock(
+            ctx.accounts.computation_account.key(),
+            &ctx.accounts.mxe_account,
+        )? {
+            msg!("Blind-Link: Registration computed against a stale registry; discarded");
+            registry.record_failure(FAILURE_STALE_WRITE);
+            ctx.accounts
+                .registration
+                .close(ctx.accounts.user.to_account_info())?;
+
+            emit!(RegistrationFailedEvent {
+                registry: registry.key(),
+                user,
+                computation_offset: queued_offset,
+                reason: FAILURE_STALE_WRITE,
+            });
+            return Ok(());
+        }
+        let computation_offset = registry.release_write_lock();
+
         let verified = match output.verify_output(
             &ctx.accounts.cluster_account,
             &ctx.accounts.computation_account,
@@ -315,19 +1932,74 @@ pub mod blind_link {
             Ok(out) => out,
             Err(e) => {
                 msg!("Blind-Link: Registration verification failed: {}", e);
-                return Err(ErrorCode::VerificationFailed.into());
+                let registry = &mut ctx.accounts.registry_state;
+                registry.record_failure(FAILURE_VERIFICATION);
+                ctx.accounts
+                    .registration
+                    .close(ctx.accounts.user.to_account_info())?;
+
+                emit!(RegistrationFailedEvent {
+                    registry: registry.key(),
+                    user,
+                    computation_offset,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
             }
         };
 
-        // Update registry with new encrypted state from MXE
+        // Update registry header and shards with new encrypted state from MXE
+        let outputs = &verified.field_0;
+        let receipt = &outputs.field_5;
         let registry = &mut ctx.accounts.registry_state;
-        registry.encrypted_data = verified
-            .field_0
-            .ciphertexts
-            .iter()
-            .flat_map(|c| c.to_vec())
-            .collect();
-        registry.nonce = u128::from_le_bytes(verified.field_0.nonce.to_le_bytes());
+        if !outputs.field_6 {
+            // Nothing was inserted, so the registry is left as it was
+            msg!("Blind-Link: Registered hash differs from the attested hash; rejected");
+            ctx.accounts
+                .registration
+                .close(ctx.accounts.user.to_account_info())?;
+
+            emit!(RegistrationFailedEvent {
+                registry: registry.key(),
+                user,
+                computation_offset,
+                reason: FAILURE_ATTESTATION_MISMATCH,
+            });
+            return Ok(());
+        }
+        if receipt.ciphertexts.len() != RECEIPT_CIPHERTEXTS
+            || !store_registry_output(
+                registry,
+                ctx.remaining_accounts,
+                (&outputs.field_0.ciphertexts[..], outputs.field_0.nonce),
+                &[
+                    (&outputs.field_1.ciphertexts[..], outputs.field_1.nonce),
+                    (&outputs.field_2.ciphertexts[..], outputs.field_2.nonce),
+                    (&outputs.field_3.ciphertexts[..], outputs.field_3.nonce),
+                    (&outputs.field_4.ciphertexts[..], outputs.field_4.nonce),
+                ],
+                computation_offset,
+            )?
+        {
+            registry.record_failure(FAILURE_OUTPUT_SIZE);
+            ctx.accounts
+                .registration
+                .close(ctx.accounts.user.to_account_info())?;
+
+            emit!(RegistrationFailedEvent {
+                registry: registry.key(),
+                user,
+                computation_offset: queued_offset,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        }
+        ctx.accounts.registration.store_receipt(
+            &receipt.ciphertexts,
+            receipt.nonce,
+            computation_offset,
+            Clock::get()?.unix_timestamp,
+        );
 
         // Note: Actual user count is encrypted in MXE state; cannot be read here
         emit!(UserRegisteredEvent {
@@ -344,17 +2016,15 @@ pub mod blind_link {
         ctx: Context<RevealRegistrySize>,
         computation_offset: u64,
     ) -> Result<()> {
-        let registry_key = ctx.accounts.registry_state.key();
-        let registry_data_offset = 8 + 1;
-        let registry_data_len = ctx.accounts.registry_state.encrypted_data.len();
-
-        let args = ArgBuilder::new()
-            .account(
-                registry_key,
-                registry_data_offset as u32,
-                registry_data_len as u32,
-            )
-            .build();
+        ctx.accounts.registry_state.reveal_offset = Some(computation_offset);
+        let registry = &ctx.accounts.registry_state;
+        let args = registry_account_arg(
+            ArgBuilder::new(),
+            registry.key(),
+            &registry.encrypted_data,
+            registry.nonce,
+        )
+        .build();
 
         // Initialize sign PDA bump for CPI signing
         ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
@@ -366,7 +2036,16 @@ pub mod blind_link {
             vec![RevealRegistrySizeCallback::callback_ix(
                 computation_offset,
                 &ctx.accounts.mxe_account,
-                &[],
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.registry_state.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.payer.key(),
+                        is_writable: false,
+                    },
+                ],
             )?],
             1,
             0,
@@ -387,7 +2066,20 @@ pub mod blind_link {
             Ok(out) => out,
             Err(e) => {
                 msg!("Blind-Link: Size reveal failed: {}", e);
-                return Err(ErrorCode::VerificationFailed.into());
+                let registry = &mut ctx.accounts.registry_state;
+                registry.record_failure(FAILURE_VERIFICATION);
+
+                emit!(RegistrySizeFailedEvent {
+                    registry: registry.key(),
+                    user: ctx.accounts.payer.key(),
+                    computation_offset: computation_offset_of(
+                        registry.reveal_offset,
+                        ctx.accounts.computation_account.key(),
+                        &ctx.accounts.mxe_account,
+                    )?,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
             }
         };
 
@@ -399,20 +2091,131 @@ pub mod blind_link {
         Ok(())
     }
 
+    /// Return the aggregate number of intersections suppressed by the
+    /// per-query match cap, for abuse monitoring, encrypted to the
+    /// authority's x25519 `pub_key` and emitted in an AnomalyCountEvent.
+    /// Authority only; nobody else can read the count.
+    pub fn reveal_anomaly_count(
+        ctx: Context<RevealAnomalyCount>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        // Arg 1 (Shared): the authority's key and nonce for the result
+        // Arg 2 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
+        let registry = &ctx.accounts.registry_state;
+        let args = registry_account_arg(
+            ArgBuilder::new()
+                .x25519_pubkey(pub_key)
+                .plaintext_u128(nonce),
+            registry.key(),
+            &registry.encrypted_data,
+            registry.nonce,
+        )
+        .build();
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![RevealAnomalyCountCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.registry_state.key(),
+                    is_writable: false,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    /// Callback for reveal_anomaly_count: emits the count, still encrypted
+    /// to the authority, in an AnomalyCountEvent. The registry is only read,
+    /// so a failed reveal is reported in AnomalyCountFailedEvent without
+    /// touching its failure counters.
+    #[arcium_callback(encrypted_ix = "reveal_anomaly_count")]
+    pub fn reveal_anomaly_count_callback(
+        ctx: Context<RevealAnomalyCountCallback>,
+        output: SignedComputationOutputs<RevealAnomalyCountOutput>,
+    ) -> Result<()> {
+        let registry = ctx.accounts.registry_state.key();
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Anomaly count reveal failed: {}", e);
+                emit!(AnomalyCountFailedEvent {
+                    registry,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
+            }
+        };
+
+        let [result_ciphertext] = verified.field_0.ciphertexts[..] else {
+            emit!(AnomalyCountFailedEvent {
+                registry,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        };
+
+        emit!(AnomalyCountEvent {
+            result_ciphertext,
+            result_nonce: verified.field_0.nonce.to_le_bytes(),
+        });
+
+        msg!("Blind-Link: Anomaly count revealed to authority");
+        Ok(())
+    }
+
     // ── 7. Bootstrap Registry ───────────────────────────────────────
 
     /// Queue MXE computation to create initial encrypted registry state.
-    /// Must be called once after deployment, before any register_user.
+    /// Must be called once by the registry authority after deployment,
+    /// before any register_user. Re-bootstrapping a live registry wipes
+    /// every registered user, so it is only allowed after arm_registry_reset.
+    /// Every registry shard must already exist and is passed as a remaining
+    /// account, in index order.
     pub fn queue_init_registry(
         ctx: Context<QueueInitRegistry>,
         computation_offset: u64,
     ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        require!(
+            registry.paused & PAUSE_REGISTRATION == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            !registry.bootstrapped || registry.reset_armed,
+            ErrorCode::RegistryAlreadyBootstrapped
+        );
+        // A reset authorizes exactly one re-bootstrap
+        registry.reset_armed = false;
+        registry.acquire_write_lock(computation_offset, Clock::get()?.unix_timestamp)?;
+
         // Initialize sign PDA bump for CPI signing
         ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
         // No encrypted inputs — the circuit creates state from scratch
         let args = ArgBuilder::new().build();
 
+        load_registry_shards(ctx.remaining_accounts)?;
+        let mut callback_accounts = vec![CallbackAccount {
+            pubkey: ctx.accounts.registry_state.key(),
+            is_writable: true,
+        }];
+        callback_accounts.extend(registry_shard_callback_accounts(ctx.remaining_accounts));
+
         queue_computation(
             ctx.accounts,
             computation_offset,
@@ -420,12 +2223,9 @@ pub mod blind_link {
             vec![InitRegistryCallback::callback_ix(
                 computation_offset,
                 &ctx.accounts.mxe_account,
-                &[CallbackAccount {
-                    pubkey: ctx.accounts.registry_state.key(),
-                    is_writable: true,
-                }],
+                &callback_accounts,
             )?],
-            1,
+            REGISTRY_WRITE_CALLBACK_TXS,
             0,
         )?;
 
@@ -439,6 +2239,27 @@ pub mod blind_link {
         ctx: Context<InitRegistryCallback>,
         output: SignedComputationOutputs<InitRegistryOutput>,
     ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        if !registry.holds_write_lock(
+            ctx.accounts.computation_account.key(),
+            &ctx.accounts.mxe_account,
+        )? {
+            msg!("Blind-Link: Bootstrap no longer holds the registry write lock; discarded");
+            registry.record_failure(FAILURE_STALE_WRITE);
+
+            emit!(BootstrapFailedEvent {
+                registry: registry.key(),
+                authority: registry.authority,
+                computation_offset: registry.reclaimed_offset(
+                    ctx.accounts.computation_account.key(),
+                    &ctx.accounts.mxe_account,
+                )?,
+                reason: FAILURE_STALE_WRITE,
+            });
+            return Ok(());
+        }
+        let computation_offset = registry.release_write_lock();
+
         let verified = match output.verify_output(
             &ctx.accounts.cluster_account,
             &ctx.accounts.computation_account,
@@ -446,18 +2267,44 @@ pub mod blind_link {
             Ok(out) => out,
             Err(e) => {
                 msg!("Blind-Link: Registry init verification failed: {}", e);
-                return Err(ErrorCode::VerificationFailed.into());
+                let registry = &mut ctx.accounts.registry_state;
+                registry.record_failure(FAILURE_VERIFICATION);
+
+                emit!(BootstrapFailedEvent {
+                    registry: registry.key(),
+                    authority: registry.authority,
+                    computation_offset,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
             }
         };
 
+        let outputs = &verified.field_0;
         let registry = &mut ctx.accounts.registry_state;
-        registry.encrypted_data = verified
-            .field_0
-            .ciphertexts
-            .iter()
-            .flat_map(|c| c.to_vec())
-            .collect();
-        registry.nonce = u128::from_le_bytes(verified.field_0.nonce.to_le_bytes());
+        if !store_registry_output(
+            registry,
+            ctx.remaining_accounts,
+            (&outputs.field_0.ciphertexts[..], outputs.field_0.nonce),
+            &[
+                (&outputs.field_1.ciphertexts[..], outputs.field_1.nonce),
+                (&outputs.field_2.ciphertexts[..], outputs.field_2.nonce),
+                (&outputs.field_3.ciphertexts[..], outputs.field_3.nonce),
+                (&outputs.field_4.ciphertexts[..], outputs.field_4.nonce),
+            ],
+            computation_offset,
+        )? {
+            registry.record_failure(FAILURE_OUTPUT_SIZE);
+
+            emit!(BootstrapFailedEvent {
+                registry: registry.key(),
+                authority: registry.authority,
+                computation_offset,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        }
+        registry.bootstrapped = true;
 
         msg!(
             "Blind-Link: Registry bootstrapped with MXE-encrypted initial state ({} bytes)",
@@ -466,35 +2313,3276 @@ pub mod blind_link {
         Ok(())
     }
 
-    // ── Comp Def Offsets ────────────────────────────────────────────────────
-
-    const COMP_DEF_OFFSET_INTERSECT_CONTACTS: u32 = comp_def_offset("intersect_contacts");
-    const COMP_DEF_OFFSET_REGISTER_USER: u32 = comp_def_offset("register_user");
-    const COMP_DEF_OFFSET_REVEAL_REGISTRY_SIZE: u32 = comp_def_offset("reveal_registry_size");
-    const COMP_DEF_OFFSET_INIT_REGISTRY: u32 = comp_def_offset("init_registry");
+    // ── 8. Session Cleanup ──────────────────────────────────────────
+
+    /// Close a finished PSI session and reclaim its rent.
+    /// The owner should read (or have already decrypted) the result first.
+    pub fn close_session(ctx: Context<CloseSession>) -> Result<()> {
+        require!(
+            ctx.accounts.psi_session.status != SessionStatus::Computing,
+            ErrorCode::SessionStillComputing
+        );
+
+        msg!(
+            "Blind-Link: PSI session closed (offset: {})",
+            ctx.accounts.psi_session.computation_offset
+        );
+        Ok(())
+    }
+
+    /// Abandon a PSI session that is still waiting on the MXE and reclaim
+    /// its rent. Refused while its computation holds the registry write
+    /// lock, since only that computation's callback releases it; once the
+    /// lock is reclaimed or expire_session has released it, a late callback
+    /// fails harmlessly because the session account no longer exists.
+    pub fn cancel_session(ctx: Context<CancelSession>) -> Result<()> {
+        let session = &mut ctx.accounts.psi_session;
+        require!(
+            ctx.accounts.registry_state.write_lock_offset
+                != Some(session.active_computation_offset),
+            ErrorCode::SessionHoldsWriteLock
+        );
+        session.transition(SessionStatus::Cancelled)?;
+
+        emit!(PsiCancelledEvent {
+            user: session.user,
+            computation_offset: session.computation_offset,
+        });
+
+        msg!(
+            "Blind-Link: PSI session cancelled (offset: {})",
+            session.computation_offset
+        );
+        Ok(())
+    }
+
+    /// Permissionless crank: fail a session whose MXE callback never arrived
+    /// within the registry's session timeout, and release the registry
+    /// write lock if the expired computation still holds it. A session with
+    /// retries left stays open as Expired, so its owner can retry_intersect
+    /// it or reclaim its rent with close_session; once the retry limit is
+    /// reached the session is closed and its rent refunded to the user.
+    pub fn expire_session(ctx: Context<ExpireSession>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let session = &mut ctx.accounts.psi_session;
+        let registry = &mut ctx.accounts.registry_state;
+        session.expire(now, registry.session_timeout_secs)?;
+        registry.abandon_write_lock(session.active_computation_offset);
+
+        let closed = session.retries_exhausted();
+        emit!(PsiExpiredEvent {
+            user: session.user,
+            computation_offset: session.computation_offset,
+            active_computation_offset: session.active_computation_offset,
+            created_at: session.created_at,
+            expired_at: now,
+            closed,
+        });
+        if closed {
+            session.close(ctx.accounts.user.to_account_info())?;
+        }
+
+        msg!(
+            "Blind-Link: PSI session expired (offset: {})",
+            session.computation_offset
+        );
+        Ok(())
+    }
+
+    // ── 9. Registry Administration ──────────────────────────────────
+
+    /// Pause the operations selected by `flags` (PAUSE_REGISTRATION and/or
+    /// PAUSE_DISCOVERY). Computations already queued still complete.
+    pub fn pause_registry(ctx: Context<RegistryAdmin>, flags: u8) -> Result<()> {
+        require!(flags & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+
+        let registry = &mut ctx.accounts.registry_state;
+        registry.paused |= flags;
+
+        emit!(RegistryPauseEvent {
+            paused: registry.paused,
+        });
+
+        msg!(
+            "Blind-Link: Registry paused (flags: {:#04b})",
+            registry.paused
+        );
+        Ok(())
+    }
+
+    /// Clear the pause flags selected by `flags`.
+    pub fn unpause_registry(ctx: Context<RegistryAdmin>, flags: u8) -> Result<()> {
+        require!(flags & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+
+        let registry = &mut ctx.accounts.registry_state;
+        registry.paused &= !flags;
+
+        emit!(RegistryPauseEvent {
+            paused: registry.paused,
+        });
+
+        msg!(
+            "Blind-Link: Registry unpaused (flags: {:#04b})",
+            registry.paused
+        );
+        Ok(())
+    }
+
+    /// Require registrations to be attested by `attestation_key` (see
+    /// register_user), or stop requiring attestation with None.
+    pub fn set_attestation_key(
+        ctx: Context<RegistryAdmin>,
+        attestation_key: Option<Pubkey>,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        registry.attestation_key = attestation_key;
+
+        emit!(AttestationKeyUpdatedEvent { attestation_key });
+
+        msg!("Blind-Link: Attestation key set to {:?}", attestation_key);
+        Ok(())
+    }
+
+    /// Set how long a PSI session may stay computing before expire_session
+    /// can reap it.
+    pub fn set_session_timeout(ctx: Context<RegistryAdmin>, timeout_secs: i64) -> Result<()> {
+        require!(timeout_secs > 0, ErrorCode::InvalidSessionTimeout);
+
+        let registry = &mut ctx.accounts.registry_state;
+        registry.session_timeout_secs = timeout_secs;
+
+        msg!("Blind-Link: Session timeout set to {}s", timeout_secs);
+        Ok(())
+    }
+
+    /// Cap how many matches each registry entry may report per epoch of
+    /// `epoch_secs`, to make repeatedly confirming one registrant costly.
+    /// 0 disables the cap. The counters roll over with the epoch, so a
+    /// new `epoch_secs` starts a fresh epoch.
+    ///
+    /// The cap is per entry, not per querier: anyone who knows a
+    /// registrant's identifier can spend it with their own queries, hiding
+    /// that registrant from every other querier until the epoch rolls
+    /// over. Pick the cap and epoch with that griefing cost in mind.
+    pub fn set_match_rate_limit(
+        ctx: Context<RegistryAdmin>,
+        matches_per_epoch: u64,
+        epoch_secs: i64,
+    ) -> Result<()> {
+        require!(epoch_secs > 0, ErrorCode::InvalidMatchEpoch);
+
+        let registry = &mut ctx.accounts.registry_state;
+        registry.match_cap_per_epoch = matches_per_epoch;
+        registry.match_epoch_secs = epoch_secs;
+
+        emit!(MatchRateLimitUpdatedEvent {
+            matches_per_epoch,
+            epoch_secs,
+        });
+
+        msg!(
+            "Blind-Link: Match cap set to {} per {}s epoch",
+            matches_per_epoch,
+            epoch_secs
+        );
+        Ok(())
+    }
+
+    /// Cap how many contacts one intersection may match. A query over the
+    /// cap, a strong sign of someone probing known registrants, is answered
+    /// with no matches and counted in the encrypted anomaly counter (see
+    /// reveal_anomaly_count). 0 disables the cap.
+    pub fn set_query_match_cap(ctx: Context<RegistryAdmin>, max_matches: u64) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        registry.max_matches_per_query = max_matches;
+
+        emit!(QueryMatchCapUpdatedEvent { max_matches });
+
+        msg!("Blind-Link: Query match cap set to {}", max_matches);
+        Ok(())
+    }
+
+    /// Step 1 of an authority transfer: nominate `new_authority`.
+    /// The nominee must call accept_authority before the transfer takes effect.
+    pub fn transfer_authority(ctx: Context<RegistryAdmin>, new_authority: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        registry.pending_authority = Some(new_authority);
+
+        msg!(
+            "Blind-Link: Authority transfer proposed to {}",
+            new_authority
+        );
+        Ok(())
+    }
+
+    /// Step 2 of an authority transfer: the nominee accepts the role.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        let previous = registry.authority;
+        registry.authority = ctx.accounts.new_authority.key();
+        registry.pending_authority = None;
+
+        emit!(AuthorityTransferredEvent {
+            previous_authority: previous,
+            new_authority: registry.authority,
+        });
+
+        msg!(
+            "Blind-Link: Authority transferred to {}",
+            registry.authority
+        );
+        Ok(())
+    }
+
+    /// Authorize one re-bootstrap of an already bootstrapped registry.
+    /// The next queue_init_registry call consumes the authorization and,
+    /// once its callback lands, replaces the registry with empty state.
+    pub fn arm_registry_reset(ctx: Context<RegistryAdmin>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        require!(registry.bootstrapped, ErrorCode::RegistryNotBootstrapped);
+        registry.reset_armed = true;
+
+        emit!(RegistryResetArmedEvent {
+            registry: registry.key(),
+            authority: registry.authority,
+        });
+
+        msg!("Blind-Link: Registry reset armed");
+        Ok(())
+    }
+
+    /// Reallocate the registry account to the size the circuit layout
+    /// requires (RegistryState::SPACE); the authority pays for growth and
+    /// is refunded on shrink. The runtime caps growth per instruction, so a
+    /// large increase takes several calls.
+    pub fn resize_registry(ctx: Context<ResizeRegistry>) -> Result<()> {
+        msg!(
+            "Blind-Link: Registry account resized to {} bytes (required: {})",
+            ctx.accounts.registry_state.to_account_info().data_len(),
+            RegistryState::SPACE
+        );
+        Ok(())
+    }
+
+    /// Reallocate registry shard `index` to RegistryShard::SPACE, like
+    /// resize_registry.
+    pub fn resize_registry_shard(ctx: Context<ResizeRegistryShard>, index: u8) -> Result<()> {
+        msg!(
+            "Blind-Link: Registry shard {} resized to {} bytes (required: {})",
+            index,
+            ctx.accounts.registry_shard.to_account_info().data_len(),
+            RegistryShard::SPACE
+        );
+        Ok(())
+    }
+
+    /// Close registry shard `index` and return its rent to the authority.
+    /// The registry can no longer run computations, so it is marked as not
+    /// bootstrapped; recreating the shard requires a fresh bootstrap.
+    pub fn close_registry_shard(ctx: Context<CloseRegistryShard>, index: u8) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        require!(
+            registry.write_lock_offset.is_none(),
+            ErrorCode::RegistryWriteLocked
+        );
+        registry.bootstrapped = false;
+        registry.reset_armed = false;
+
+        msg!("Blind-Link: Registry shard {} closed", index);
+        Ok(())
+    }
+
+    /// Close the Global Registry and return its rent to the authority.
+    /// All encrypted registry state is discarded. Every shard must already
+    /// be closed (see close_registry_shard) and passed as a remaining
+    /// account, in index order, and the registration queue must be empty,
+    /// so no registrant's entry or stake is orphaned.
+    pub fn close_registry(ctx: Context<CloseRegistry>) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.write_lock_offset.is_none(),
+            ErrorCode::RegistryWriteLocked
+        );
+
+        require!(
+            ctx.remaining_accounts.len() == NUM_REGISTRY_SHARDS,
+            ErrorCode::InvalidRegistryShards
+        );
+        for (index, info) in ctx.remaining_accounts.iter().enumerate() {
+            let (expected, _) =
+                Pubkey::find_program_address(&[REGISTRY_SHARD_SEED, &[index as u8]], &crate::ID);
+            require_keys_eq!(*info.key, expected, ErrorCode::InvalidRegistryShards);
+            require!(info.data_is_empty(), ErrorCode::RegistryNotEmpty);
+        }
+
+        let queue_info = ctx.accounts.registration_queue.to_account_info();
+        if !queue_info.data_is_empty() {
+            let queue =
+                RegistrationQueue::try_deserialize(&mut &queue_info.try_borrow_data()?[..])?;
+            require!(
+                queue.entries.is_empty() && queue.in_flight == 0,
+                ErrorCode::RegistryNotEmpty
+            );
+        }
+
+        msg!("Blind-Link: Global Registry closed");
+        Ok(())
+    }
+
+    // ── 10. Registration Queue ──────────────────────────────────────
+
+    /// Create the registration queue. Called once by the registry authority.
+    pub fn initialize_registration_queue(ctx: Context<InitializeRegistrationQueue>) -> Result<()> {
+        let queue = &mut ctx.accounts.registration_queue;
+        queue.bump = ctx.bumps.registration_queue;
+        queue.entries = vec![];
+        queue.in_flight = 0;
+
+        msg!("Blind-Link: Registration queue initialized");
+        Ok(())
+    }
+
+    /// Append an encrypted registration to the queue. No MPC computation is
+    /// queued; the entry is inserted by the next process_registration_queue.
+    /// Charges the registration fee, requires a staked escrow and, when
+    /// configured, an attestation, and opens a Pending Registration, like
+    /// register_user. The attested hash is queued with the entry.
+    pub fn enqueue_registration(
+        ctx: Context<EnqueueRegistration>,
+        encrypted_hash: [u8; 32],
+        encrypted_owner_secret: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+        attested: Option<AttestedHash>,
+        attestation_expiry: i64,
+    ) -> Result<()> {
+        let registry = &ctx.accounts.registry_state;
+        require!(
+            registry.paused & PAUSE_REGISTRATION == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(registry.bootstrapped, ErrorCode::RegistryNotBootstrapped);
+        let attested = require_attestation(
+            registry,
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.user.key(),
+            attested,
+            AttestedHash {
+                encrypted_hash,
+                pub_key,
+                nonce,
+            },
+            attestation_expiry,
+        )?;
+
+        require!(
+            ctx.accounts.registration_queue.entries.len() < REGISTRATION_QUEUE_CAPACITY,
+            ErrorCode::RegistrationQueueFull
+        );
+
+        let registration_fee = ctx.accounts.fees.fee_config.registration_fee;
+        ctx.accounts.fees.collect(
+            &ctx.accounts.user,
+            &ctx.accounts.system_program,
+            registration_fee,
+        )?;
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts
+            .registration
+            .open(ctx.bumps.registration, ctx.accounts.user.key(), 0, now);
+
+        let queue = &mut ctx.accounts.registration_queue;
+        queue.entries.push(PendingRegistration {
+            user: ctx.accounts.user.key(),
+            encrypted_hash,
+            encrypted_owner_secret,
+            pub_key,
+            nonce,
+            attested,
+            enqueued_at: now,
+        });
+
+        emit!(RegistrationEnqueuedEvent {
+            user: ctx.accounts.user.key(),
+            position: (queue.entries.len() - 1) as u32,
+        });
+
+        msg!(
+            "Blind-Link: Registration enqueued ({} pending)",
+            queue.entries.len()
+        );
+        Ok(())
+    }
+
+    /// Permissionless crank: fold the oldest REGISTRATION_BATCH_SIZE queued
+    /// registrations into the Global Registry in one MPC computation.
+    /// Takes the registry write lock and the registry shards (as remaining
+    /// accounts) like register_user; each batch entry's Registration and
+    /// wallet follow the shards as a pair, in queue order, so the callback
+    /// can store the receipt or close a rejected Registration to the
+    /// wallet. Refused while
+    /// registration is paused or the registry is not bootstrapped, so a
+    /// batch is never folded into missing shard state.
+    pub fn process_registration_queue(
+        ctx: Context<ProcessRegistrationQueue>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_REGISTRATION == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            ctx.accounts.registry_state.bootstrapped,
+            ErrorCode::RegistryNotBootstrapped
+        );
+        let queue = &mut ctx.accounts.registration_queue;
+        require!(!queue.entries.is_empty(), ErrorCode::RegistrationQueueEmpty);
+
+        // Holding the lock means any earlier batch lost it and will be
+        // discarded by its callback, so its entries can be re-batched.
+        ctx.accounts
+            .registry_state
+            .acquire_write_lock(computation_offset, Clock::get()?.unix_timestamp)?;
+
+        let batch = queue.entries.len().min(REGISTRATION_BATCH_SIZE);
+        queue.in_flight = batch as u8;
+
+        require!(
+            ctx.remaining_accounts.len() == NUM_REGISTRY_SHARDS + 2 * batch,
+            ErrorCode::InvalidRegistration
+        );
+        let (shard_accounts, registrant_accounts) =
+            ctx.remaining_accounts.split_at(NUM_REGISTRY_SHARDS);
+        for (pair, entry) in registrant_accounts.chunks(2).zip(&queue.entries) {
+            let registration = load_registration(&pair[0], &entry.user)?;
+            require!(
+                registration.status == RegistrationStatus::Pending,
+                ErrorCode::InvalidRegistration
+            );
+            require_keys_eq!(*pair[1].key, entry.user, ErrorCode::InvalidRegistration);
+        }
+
+        // Arg 1-4 (Enc<Shared, RegistrationEntry>): one per batch slot. Unused
+        // slots repeat the first entry; the circuit ignores them.
+        let mut arg_builder = ArgBuilder::new();
+        for i in 0..REGISTRATION_BATCH_SIZE {
+            let entry = &queue.entries[if i < batch { i } else { 0 }];
+            arg_builder = arg_builder
+                .x25519_pubkey(entry.pub_key)
+                .plaintext_u128(entry.nonce)
+                .encrypted_u128(entry.encrypted_hash)
+                .encrypted_u128(entry.encrypted_owner_secret);
+        }
+        // Arg 5-8 (Enc<Shared, ContactHash>): each slot's attested hash
+        for i in 0..REGISTRATION_BATCH_SIZE {
+            let entry = &queue.entries[if i < batch { i } else { 0 }];
+            arg_builder = attested_hash_arg(arg_builder, &entry.attested);
+        }
+
+        // Arg 9 (u64): number of live entries in the batch
+        // Arg 10 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
+        // Arg 11-14 (Enc<Mxe, RegistryShard>): read from the registry shards
+        let registry = &ctx.accounts.registry_state;
+        let registry_key = registry.key();
+
+        let args = registry_shard_args(
+            registry_account_arg(
+                arg_builder.plaintext_u64(batch as u64),
+                registry_key,
+                &registry.encrypted_data,
+                registry.nonce,
+            ),
+            shard_accounts,
+        )?
+        .build();
+
+        let mut callback_accounts = vec![
+            CallbackAccount {
+                pubkey: registry_key,
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.registration_queue.key(),
+                is_writable: true,
+            },
+        ];
+        // Shards, then each entry's Registration and wallet; all are written
+        // by the callback
+        callback_accounts.extend(registry_shard_callback_accounts(shard_accounts));
+        callback_accounts.extend(registrant_accounts.iter().map(|info| CallbackAccount {
+            pubkey: *info.key,
+            is_writable: true,
+        }));
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![ProcessRegistrationQueueCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &callback_accounts,
+            )?],
+            REGISTRY_WRITE_CALLBACK_TXS,
+            0,
+        )?;
+
+        msg!("Blind-Link: Registration batch queued ({} entries)", batch);
+        Ok(())
+    }
+
+    /// Callback for process_registration_queue: stores the updated registry,
+    /// hands each processed entry's Registration its receipt, and drains the
+    /// entries. On failure the entries stay queued for the next crank.
+    /// An entry that did not match its attested hash was not inserted: its
+    /// Registration is closed to the wallet, as in register_user_callback,
+    /// and a RegistrationFailedEvent is emitted.
+    #[arcium_callback(encrypted_ix = "process_registration_queue")]
+    pub fn process_registration_queue_callback(
+        ctx: Context<ProcessRegistrationQueueCallback>,
+        output: SignedComputationOutputs<ProcessRegistrationQueueOutput>,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        if !registry.holds_write_lock(
+            ctx.accounts.computation_account.key(),
+            &ctx.accounts.mxe_account,
+        )? {
+            // A newer batch owns the queue head now; leave it untouched
+            msg!("Blind-Link: Registration batch no longer holds the write lock; discarded");
+            registry.record_failure(FAILURE_STALE_WRITE);
+
+            emit!(RegistrationFailedEvent {
+                registry: registry.key(),
+                user: ctx.accounts.registration_queue.key(),
+                computation_offset: registry.reclaimed_offset(
+                    ctx.accounts.computation_account.key(),
+                    &ctx.accounts.mxe_account,
+                )?,
+                reason: FAILURE_STALE_WRITE,
+            });
+            return Ok(());
+        }
+        let computation_offset = registry.release_write_lock();
+
+        let queue = &mut ctx.accounts.registration_queue;
+        let batch = queue.in_flight as usize;
+        queue.in_flight = 0;
+
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Registration batch verification failed: {}", e);
+                let registry = &mut ctx.accounts.registry_state;
+                registry.record_failure(FAILURE_VERIFICATION);
+
+                for entry in queue.entries.iter().take(batch) {
+                    emit!(RegistrationFailedEvent {
+                        registry: registry.key(),
+                        user: entry.user,
+                        computation_offset,
+                        reason: FAILURE_VERIFICATION,
+                    });
+                }
+                return Ok(());
+            }
+        };
+
+        let outputs = &verified.field_0;
+        let receipts = [
+            &outputs.field_5,
+            &outputs.field_6,
+            &outputs.field_7,
+            &outputs.field_8,
+        ];
+        let accepted = [
+            outputs.field_9,
+            outputs.field_10,
+            outputs.field_11,
+            outputs.field_12,
+        ];
+        let (shard_accounts, registrant_accounts) = ctx
+            .remaining_accounts
+            .split_at(NUM_REGISTRY_SHARDS.min(ctx.remaining_accounts.len()));
+        let registry = &mut ctx.accounts.registry_state;
+        if registrant_accounts.len() != 2 * batch
+            || receipts
+                .iter()
+                .any(|r| r.ciphertexts.len() != RECEIPT_CIPHERTEXTS)
+            || !store_registry_output(
+                registry,
+                shard_accounts,
+                (&outputs.field_0.ciphertexts[..], outputs.field_0.nonce),
+                &[
+                    (&outputs.field_1.ciphertexts[..], outputs.field_1.nonce),
+                    (&outputs.field_2.ciphertexts[..], outputs.field_2.nonce),
+                    (&outputs.field_3.ciphertexts[..], outputs.field_3.nonce),
+                    (&outputs.field_4.ciphertexts[..], outputs.field_4.nonce),
+                ],
+                computation_offset,
+            )?
+        {
+            registry.record_failure(FAILURE_OUTPUT_SIZE);
+
+            for entry in queue.entries.iter().take(batch) {
+                emit!(RegistrationFailedEvent {
+                    registry: registry.key(),
+                    user: entry.user,
+                    computation_offset,
+                    reason: FAILURE_OUTPUT_SIZE,
+                });
+            }
+            return Ok(());
+        }
+
+        // Padding slots' receipts have no registration and are dropped
+        let now = Clock::get()?.unix_timestamp;
+        for (((pair, entry), receipt), accepted) in registrant_accounts
+            .chunks(2)
+            .zip(&queue.entries)
+            .zip(receipts)
+            .zip(accepted)
+        {
+            let (info, wallet) = (&pair[0], &pair[1]);
+            require!(
+                info.is_writable && wallet.is_writable,
+                ErrorCode::InvalidRegistration
+            );
+            require_keys_eq!(*wallet.key, entry.user, ErrorCode::InvalidRegistration);
+            let mut registration = load_registration(info, &entry.user)?;
+            if !accepted {
+                close_remaining_account(info, wallet)?;
+
+                emit!(RegistrationFailedEvent {
+                    registry: registry.key(),
+                    user: entry.user,
+                    computation_offset,
+                    reason: FAILURE_ATTESTATION_MISMATCH,
+                });
+                continue;
+            }
+            registration.store_receipt(
+                &receipt.ciphertexts,
+                receipt.nonce,
+                computation_offset,
+                now,
+            );
+            registration.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        }
+        queue.entries.drain(..batch);
+
+        emit!(RegistrationBatchProcessedEvent {
+            registry: registry.key(),
+            processed: batch as u8,
+            remaining: queue.entries.len() as u32,
+        });
+
+        msg!(
+            "Blind-Link: Registration batch folded into Global Registry ({} entries)",
+            batch
+        );
+        Ok(())
+    }
+
+    // ── 11. Fees ────────────────────────────────────────────────────
+
+    /// Create the fee config and fund the treasury PDA with its rent-exempt
+    /// minimum so lamport fees of any size can be deposited. Fees start in
+    /// lamports; see set_fee_mint. The query fee must be positive: it is
+    /// what each hold of the registry write lock by intersect_contacts
+    /// costs.
+    pub fn initialize_fee_config(
+        ctx: Context<InitializeFeeConfig>,
+        query_fee: u64,
+        registration_fee: u64,
+    ) -> Result<()> {
+        require!(query_fee > 0, ErrorCode::InvalidQueryFee);
+        let config = &mut ctx.accounts.fee_config;
+        config.bump = ctx.bumps.fee_config;
+        config.treasury_bump = ctx.bumps.treasury;
+        config.fee_mint = None;
+        config.query_fee = query_fee;
+        config.registration_fee = registration_fee;
+        config.registration_stake = 0;
+        config.lamports_collected = 0;
+        config.tokens_collected = 0;
+
+        let rent_exempt = Rent::get()?.minimum_balance(0);
+        let shortfall = rent_exempt.saturating_sub(ctx.accounts.treasury.lamports());
+        if shortfall > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: ctx.accounts.treasury.to_account_info(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+
+        msg!(
+            "Blind-Link: Fee config initialized (query: {}, registration: {})",
+            query_fee,
+            registration_fee
+        );
+        Ok(())
+    }
+
+    /// Set the per-operation prices, in the current fee unit. The query fee
+    /// must stay positive (see initialize_fee_config).
+    pub fn set_fees(ctx: Context<FeeAdmin>, query_fee: u64, registration_fee: u64) -> Result<()> {
+        require!(query_fee > 0, ErrorCode::InvalidQueryFee);
+        let config = &mut ctx.accounts.fee_config;
+        config.query_fee = query_fee;
+        config.registration_fee = registration_fee;
+
+        emit!(FeesUpdatedEvent {
+            fee_mint: config.fee_mint,
+            query_fee,
+            registration_fee,
+            registration_stake: config.registration_stake,
+        });
+
+        msg!(
+            "Blind-Link: Fees set (query: {}, registration: {})",
+            query_fee,
+            registration_fee
+        );
+        Ok(())
+    }
+
+    /// Set the refundable stake new registrants deposit, in the current fee
+    /// unit. Escrows already opened keep the stake they were opened with;
+    /// one holding less must be withdrawn and re-deposited to register.
+    pub fn set_registration_stake(ctx: Context<FeeAdmin>, registration_stake: u64) -> Result<()> {
+        let config = &mut ctx.accounts.fee_config;
+        config.registration_stake = registration_stake;
+
+        emit!(FeesUpdatedEvent {
+            fee_mint: config.fee_mint,
+            query_fee: config.query_fee,
+            registration_fee: config.registration_fee,
+            registration_stake,
+        });
+
+        msg!(
+            "Blind-Link: Registration stake set to {}",
+            registration_stake
+        );
+        Ok(())
+    }
+
+    /// Charge fees in `fee_mint` tokens, creating the treasury's token
+    /// account for the mint if needed. Prices are in the mint's base units,
+    /// so call set_fees alongside this.
+    pub fn set_fee_mint(ctx: Context<SetFeeMint>) -> Result<()> {
+        let config = &mut ctx.accounts.fee_config;
+        config.fee_mint = Some(ctx.accounts.fee_mint.key());
+
+        emit!(FeesUpdatedEvent {
+            fee_mint: config.fee_mint,
+            query_fee: config.query_fee,
+            registration_fee: config.registration_fee,
+            registration_stake: config.registration_stake,
+        });
+
+        msg!(
+            "Blind-Link: Fees now charged in {}",
+            ctx.accounts.fee_mint.key()
+        );
+        Ok(())
+    }
+
+    /// Charge fees in lamports again. Tokens already collected stay in the
+    /// treasury's token account until withdrawn.
+    pub fn clear_fee_mint(ctx: Context<FeeAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.fee_config;
+        config.fee_mint = None;
+
+        emit!(FeesUpdatedEvent {
+            fee_mint: None,
+            query_fee: config.query_fee,
+            registration_fee: config.registration_fee,
+            registration_stake: config.registration_stake,
+        });
+
+        msg!("Blind-Link: Fees now charged in lamports");
+        Ok(())
+    }
+
+    /// Withdraw collected fees to the authority's chosen destination.
+    /// Withdraws tokens when the treasury token account is passed (any mint
+    /// fees were ever charged in), lamports otherwise; the treasury always
+    /// keeps its rent-exempt minimum.
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: u64) -> Result<()> {
+        let treasury_seeds: &[&[u8]] = &[TREASURY_SEED, &[ctx.accounts.fee_config.treasury_bump]];
+
+        match (
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.destination_token_account,
+            &ctx.accounts.token_program,
+        ) {
+            (Some(from), Some(to), Some(token_program)) => {
+                require!(
+                    amount <= from.amount,
+                    ErrorCode::InsufficientTreasuryBalance
+                );
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        token_program.to_account_info(),
+                        token::Transfer {
+                            from: from.to_account_info(),
+                            to: to.to_account_info(),
+                            authority: ctx.accounts.treasury.to_account_info(),
+                        },
+                        &[treasury_seeds],
+                    ),
+                    amount,
+                )?;
+            }
+            (None, None, _) => {
+                let available = ctx
+                    .accounts
+                    .treasury
+                    .lamports()
+                    .saturating_sub(Rent::get()?.minimum_balance(0));
+                require!(amount <= available, ErrorCode::InsufficientTreasuryBalance);
+                system_program::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.treasury.to_account_info(),
+                            to: ctx.accounts.destination.to_account_info(),
+                        },
+                        &[treasury_seeds],
+                    ),
+                    amount,
+                )?;
+            }
+            _ => return err!(ErrorCode::FeeTokenAccountsMissing),
+        }
+
+        emit!(FeesWithdrawnEvent {
+            authority: ctx.accounts.authority.key(),
+            fee_mint: ctx.accounts.treasury_token_account.as_ref().map(|a| a.mint),
+            amount,
+        });
+
+        msg!("Blind-Link: Withdrew {} from the treasury", amount);
+        Ok(())
+    }
+
+    // ── 12. Registration Stake ──────────────────────────────────────
+
+    /// Open the caller's registration escrow with the configured stake, in
+    /// lamports held by the escrow itself. Registration requires a funded
+    /// escrow, so every registered hash is backed by a stake.
+    pub fn deposit_stake(ctx: Context<DepositStake>) -> Result<()> {
+        let config = &ctx.accounts.fee_config;
+        require!(config.fee_mint.is_none(), ErrorCode::StakeMintMismatch);
+        let amount = config.registration_stake;
+
+        if amount > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.user.to_account_info(),
+                        to: ctx.accounts.registration_escrow.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+        }
+
+        let user = ctx.accounts.user.key();
+        ctx.accounts.registration_escrow.open(
+            ctx.bumps.registration_escrow,
+            user,
+            amount,
+            None,
+            Clock::get()?.unix_timestamp,
+        );
+
+        emit!(StakeDepositedEvent {
+            user,
+            stake_mint: None,
+            amount,
+        });
+
+        msg!("Blind-Link: Staked {} lamports", amount);
+        Ok(())
+    }
+
+    /// Open the caller's registration escrow with the configured stake in
+    /// the fee mint, held in the escrow's token vault.
+    pub fn deposit_stake_tokens(ctx: Context<DepositStakeTokens>) -> Result<()> {
+        let amount = ctx.accounts.fee_config.registration_stake;
+
+        if amount > 0 {
+            token::transfer(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: ctx.accounts.user_token_account.to_account_info(),
+                        to: ctx.accounts.escrow_vault.to_account_info(),
+                        authority: ctx.accounts.user.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+        }
+
+        let user = ctx.accounts.user.key();
+        let stake_mint = ctx.accounts.stake_mint.key();
+        ctx.accounts.registration_escrow.open(
+            ctx.bumps.registration_escrow,
+            user,
+            amount,
+            Some(stake_mint),
+            Clock::get()?.unix_timestamp,
+        );
+
+        emit!(StakeDepositedEvent {
+            user,
+            stake_mint: Some(stake_mint),
+            amount,
+        });
+
+        msg!("Blind-Link: Staked {} of {}", amount, stake_mint);
+        Ok(())
+    }
+
+    /// Remove a registration from the Global Registry using the receipt in
+    /// its Registration. Callable by the registrant, or by the registry authority
+    /// to evict an abusive registration. Takes the registry write lock and
+    /// the registry shards (as remaining accounts) like register_user.
+    pub fn deregister_user(ctx: Context<DeregisterUser>, computation_offset: u64) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_REGISTRATION == 0,
+            ErrorCode::RegistryPaused
+        );
+        ctx.accounts
+            .registry_state
+            .acquire_write_lock(computation_offset, Clock::get()?.unix_timestamp)?;
+
+        let registration = &mut ctx.accounts.registration;
+        registration.computation_offset = computation_offset;
+        registration.set_status(
+            RegistrationStatus::Deregistering,
+            Clock::get()?.unix_timestamp,
+        );
+        let registration_key = registration.key();
+
+        // Arg 1 (Enc<Mxe, RegistrationReceipt>): read from the Registration
+        // Arg 2 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
+        // Arg 3-6 (Enc<Mxe, RegistryShard>): read from the registry shards
+        let registry = &ctx.accounts.registry_state;
+        let registry_key = registry.key();
+        let args = registry_shard_args(
+            registry_account_arg(
+                registry_account_arg(
+                    ArgBuilder::new(),
+                    registration_key,
+                    &registration.encrypted_receipt,
+                    registration.receipt_nonce,
+                ),
+                registry_key,
+                &registry.encrypted_data,
+                registry.nonce,
+            ),
+            ctx.remaining_accounts,
+        )?
+        .build();
+
+        let mut callback_accounts = vec![
+            CallbackAccount {
+                pubkey: registry_key,
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: registration_key,
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: registration.user,
+                is_writable: true,
+            },
+        ];
+        callback_accounts.extend(registry_shard_callback_accounts(ctx.remaining_accounts));
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![DeregisterUserCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &callback_accounts,
+            )?],
+            REGISTRY_WRITE_CALLBACK_TXS,
+            0,
+        )?;
+
+        msg!("Blind-Link: Deregistration queued");
+        Ok(())
+    }
+
+    /// Callback for deregister_user: stores the registry without the
+    /// registration and closes the Registration, unlocking the wallet's
+    /// stake. On failure the Registration returns to Registered.
+    #[arcium_callback(encrypted_ix = "deregister_user")]
+    pub fn deregister_user_callback(
+        ctx: Context<DeregisterUserCallback>,
+        output: SignedComputationOutputs<DeregisterUserOutput>,
+    ) -> Result<()> {
+        let user = ctx.accounts.registration.user;
+        let queued_offset = ctx.accounts.registration.computation_offset;
+        let now = Clock::get()?.unix_timestamp;
+        let registry = &mut ctx.accounts.registry_state;
+        if !registry.holds_write_lock(
+            ctx.accounts.computation_account.key(),
+            &ctx.accounts.mxe_account,
+        )? {
+            msg!("Blind-Link: Deregistration computed against a stale registry; discarded");
+            registry.record_failure(FAILURE_STALE_WRITE);
+            ctx.accounts
+                .registration
+                .set_status(RegistrationStatus::Registered, now);
+
+            emit!(DeregistrationFailedEvent {
+                user,
+                computation_offset: queued_offset,
+                reason: FAILURE_STALE_WRITE,
+            });
+            return Ok(());
+        }
+        let computation_offset = registry.release_write_lock();
+
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Deregistration verification failed: {}", e);
+                ctx.accounts
+                    .registry_state
+                    .record_failure(FAILURE_VERIFICATION);
+                ctx.accounts
+                    .registration
+                    .set_status(RegistrationStatus::Registered, now);
+
+                emit!(DeregistrationFailedEvent {
+                    user,
+                    computation_offset,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
+            }
+        };
+
+        let outputs = &verified.field_0;
+        let registry = &mut ctx.accounts.registry_state;
+        if !store_registry_output(
+            registry,
+            ctx.remaining_accounts,
+            (&outputs.field_0.ciphertexts[..], outputs.field_0.nonce),
+            &[
+                (&outputs.field_1.ciphertexts[..], outputs.field_1.nonce),
+                (&outputs.field_2.ciphertexts[..], outputs.field_2.nonce),
+                (&outputs.field_3.ciphertexts[..], outputs.field_3.nonce),
+                (&outputs.field_4.ciphertexts[..], outputs.field_4.nonce),
+            ],
+            computation_offset,
+        )? {
+            registry.record_failure(FAILURE_OUTPUT_SIZE);
+            ctx.accounts
+                .registration
+                .set_status(RegistrationStatus::Registered, now);
+
+            emit!(DeregistrationFailedEvent {
+                user,
+                computation_offset,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        }
+        ctx.accounts
+            .registration
+            .close(ctx.accounts.user.to_account_info())?;
+
+        emit!(UserDeregisteredEvent {
+            registry: registry.key(),
+            user,
+        });
+
+        msg!("Blind-Link: User removed from Global Registry");
+        Ok(())
+    }
+
+    /// Settle a registration stranded by a callback that never landed, so
+    /// its stake is not locked forever. Once the session timeout has passed
+    /// since its last status change and the registry write lock no longer
+    /// names its computation, a Pending registration is closed and a
+    /// Deregistering or Updating one returns to Registered with its old
+    /// receipt. A lock its computation has held past the timeout is
+    /// reclaimed first, as the next registry write would, so a late callback
+    /// is discarded; once the wallet queues another computation, the late
+    /// callback is refused outright, since it no longer names the
+    /// registration's computation.
+    pub fn recover_registration(ctx: Context<RecoverRegistration>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let computation_offset = ctx.accounts.registration.computation_offset;
+        let registry = &mut ctx.accounts.registry_state;
+        if now
+            >= registry
+                .write_locked_at
+                .saturating_add(registry.session_timeout_secs)
+        {
+            registry.abandon_write_lock(computation_offset);
+        }
+
+        let registration = &mut ctx.accounts.registration;
+        require!(
+            registration.is_stranded(
+                registry.write_lock_offset,
+                now,
+                registry.session_timeout_secs
+            ),
+            ErrorCode::RegistrationNotStranded
+        );
+        let status = registration.status;
+        if status == RegistrationStatus::Pending {
+            registration.close(ctx.accounts.user.to_account_info())?;
+        } else {
+            registration.set_status(RegistrationStatus::Registered, now);
+        }
+
+        emit!(RegistrationRecoveredEvent {
+            user: ctx.accounts.user.key(),
+            computation_offset,
+            status,
+        });
+
+        msg!("Blind-Link: Stranded registration recovered ({:?})", status);
+        Ok(())
+    }
+
+    /// Return the remaining stake to its owner and close the escrow. Only
+    /// possible while the wallet has no Registration.
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>) -> Result<()> {
+        let escrow = &ctx.accounts.registration_escrow;
+        let amount = escrow.stake_amount;
+
+        if escrow.stake_mint.is_some() {
+            let (Some(vault), Some(to), Some(token_program)) = (
+                &ctx.accounts.escrow_vault,
+                &ctx.accounts.user_token_account,
+                &ctx.accounts.token_program,
+            ) else {
+                return err!(ErrorCode::StakeTokenAccountsMissing);
+            };
+            let seeds = escrow.signer_seeds();
+            token::transfer(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    token::Transfer {
+                        from: vault.to_account_info(),
+                        to: to.to_account_info(),
+                        authority: escrow.to_account_info(),
+                    },
+                    &[&seeds[..]],
+                ),
+                vault.amount,
+            )?;
+            token::close_account(CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                token::CloseAccount {
+                    account: vault.to_account_info(),
+                    destination: ctx.accounts.user.to_account_info(),
+                    authority: escrow.to_account_info(),
+                },
+                &[&seeds[..]],
+            ))?;
+        }
+        // A lamport stake leaves with the escrow's rent when it is closed
+
+        emit!(StakeWithdrawnEvent {
+            user: escrow.user,
+            stake_mint: escrow.stake_mint,
+            amount,
+        });
+
+        msg!("Blind-Link: Stake of {} withdrawn", amount);
+        Ok(())
+    }
+
+    /// Move a registrant's stake into the treasury in response to an abuse
+    /// report. The registration itself is removed with deregister_user.
+    pub fn slash_stake(ctx: Context<SlashStake>) -> Result<()> {
+        let escrow = &ctx.accounts.registration_escrow;
+        let amount = escrow.stake_amount;
+        require!(amount > 0, ErrorCode::NothingToSlash);
+
+        match escrow.stake_mint {
+            None => {
+                // The escrow is program-owned, so its lamports move directly
+                **escrow.to_account_info().try_borrow_mut_lamports()? -= amount;
+                **ctx
+                    .accounts
+                    .treasury
+                    .to_account_info()
+                    .try_borrow_mut_lamports()? += amount;
+            }
+            Some(stake_mint) => {
+                let (Some(vault), Some(to), Some(token_program)) = (
+                    &ctx.accounts.escrow_vault,
+                    &ctx.accounts.treasury_token_account,
+                    &ctx.accounts.token_program,
+                ) else {
+                    return err!(ErrorCode::StakeTokenAccountsMissing);
+                };
+                require_keys_eq!(to.mint, stake_mint, ErrorCode::StakeMintMismatch);
+                let seeds = escrow.signer_seeds();
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        token_program.to_account_info(),
+                        token::Transfer {
+                            from: vault.to_account_info(),
+                            to: to.to_account_info(),
+                            authority: escrow.to_account_info(),
+                        },
+                        &[&seeds[..]],
+                    ),
+                    amount,
+                )?;
+            }
+        }
+
+        let escrow = &mut ctx.accounts.registration_escrow;
+        escrow.stake_amount = 0;
+
+        emit!(StakeSlashedEvent {
+            user: escrow.user,
+            authority: ctx.accounts.authority.key(),
+            stake_mint: escrow.stake_mint,
+            amount,
+        });
+
+        msg!("Blind-Link: Stake of {} slashed", amount);
+        Ok(())
+    }
+
+    // ── 13. Registration Updates ────────────────────────────────────
+
+    /// Replace the caller's registered hash with a new one (e.g. after a
+    /// phone number change) in a single computation. The old hash is read
+    /// from the receipt in the caller's Registration; the new one is
+    /// encrypted client-side like register_user's. Charges the registration
+    /// fee and, when configured, requires an attestation over the new
+    /// registration. Takes the registry write lock and the registry shards
+    /// (as remaining accounts) like register_user.
+    pub fn update_registration(
+        ctx: Context<UpdateRegistration>,
+        computation_offset: u64,
+        encrypted_hash: [u8; 32],
+        encrypted_owner_secret: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+        attested: Option<AttestedHash>,
+        attestation_expiry: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_REGISTRATION == 0,
+            ErrorCode::RegistryPaused
+        );
+        let attested = require_attestation(
+            &ctx.accounts.registry_state,
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.user.key(),
+            attested,
+            AttestedHash {
+                encrypted_hash,
+                pub_key,
+                nonce,
+            },
+            attestation_expiry,
+        )?;
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts
+            .registry_state
+            .acquire_write_lock(computation_offset, now)?;
+
+        let registration_fee = ctx.accounts.fees.fee_config.registration_fee;
+        ctx.accounts.fees.collect(
+            &ctx.accounts.user,
+            &ctx.accounts.system_program,
+            registration_fee,
+        )?;
+
+        let registration = &mut ctx.accounts.registration;
+        registration.computation_offset = computation_offset;
+        registration.set_status(RegistrationStatus::Updating, now);
+        let registration_key = registration.key();
+
+        // Arg 1 (Enc<Mxe, RegistrationReceipt>): read from the Registration
+        // Arg 2 (Enc<Shared, RegistrationEntry>): the new hash and secret
+        // Arg 3 (Enc<Shared, ContactHash>): the attested hash
+        // Arg 4 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
+        // Arg 5-8 (Enc<Mxe, RegistryShard>): read from the registry shards
+        let registry = &ctx.accounts.registry_state;
+        let registry_key = registry.key();
+        let args = registry_shard_args(
+            registry_account_arg(
+                attested_hash_arg(
+                    registry_account_arg(
+                        ArgBuilder::new(),
+                        registration_key,
+                        &registration.encrypted_receipt,
+                        registration.receipt_nonce,
+                    )
+                    .x25519_pubkey(pub_key)
+                    .plaintext_u128(nonce)
+                    .encrypted_u128(encrypted_hash)
+                    .encrypted_u128(encrypted_owner_secret),
+                    &attested,
+                ),
+                registry_key,
+                &registry.encrypted_data,
+                registry.nonce,
+            ),
+            ctx.remaining_accounts,
+        )?
+        .build();
+
+        let mut callback_accounts = vec![
+            CallbackAccount {
+                pubkey: registry_key,
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: registration_key,
+                is_writable: true,
+            },
+        ];
+        callback_accounts.extend(registry_shard_callback_accounts(ctx.remaining_accounts));
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![UpdateRegistrationCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &callback_accounts,
+            )?],
+            REGISTRY_WRITE_CALLBACK_TXS,
+            0,
+        )?;
+
+        msg!("Blind-Link: Registration update queued");
+        Ok(())
+    }
+
+    /// Callback for update_registration: stores the registry and the new
+    /// receipt, and returns the Registration to Registered. A rejected
+    /// update (old hash absent or new bucket full) stores the unchanged
+    /// registry but keeps the old receipt; it and any other failure emit
+    /// RegistrationUpdateFailedEvent.
+    #[arcium_callback(encrypted_ix = "update_registration")]
+    pub fn update_registration_callback(
+        ctx: Context<UpdateRegistrationCallback>,
+        output: SignedComputationOutputs<UpdateRegistrationOutput>,
+    ) -> Result<()> {
+        let user = ctx.accounts.registration.user;
+        let now = Clock::get()?.unix_timestamp;
+        let registry = &mut ctx.accounts.registry_state;
+        if !registry.holds_write_lock(
+            ctx.accounts.computation_account.key(),
+            &ctx.accounts.mxe_account,
+        )? {
+            msg!("Blind-Link: Registration update computed against a stale registry; discarded");
+            registry.record_failure(FAILURE_STALE_WRITE);
+            ctx.accounts
+                .registration
+                .set_status(RegistrationStatus::Registered, now);
+
+            emit!(RegistrationUpdateFailedEvent {
+                user,
+                reason: FAILURE_STALE_WRITE,
+            });
+            return Ok(());
+        }
+        let computation_offset = registry.release_write_lock();
+
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Registration update verification failed: {}", e);
+                ctx.accounts
+                    .registry_state
+                    .record_failure(FAILURE_VERIFICATION);
+                ctx.accounts
+                    .registration
+                    .set_status(RegistrationStatus::Registered, now);
+
+                emit!(RegistrationUpdateFailedEvent {
+                    user,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
+            }
+        };
+
+        let outputs = &verified.field_0;
+        let receipt = &outputs.field_5;
+        let updated = outputs.field_6;
+        let registry = &mut ctx.accounts.registry_state;
+        if receipt.ciphertexts.len() != RECEIPT_CIPHERTEXTS
+            || !store_registry_output(
+                registry,
+                ctx.remaining_accounts,
+                (&outputs.field_0.ciphertexts[..], outputs.field_0.nonce),
+                &[
+                    (&outputs.field_1.ciphertexts[..], outputs.field_1.nonce),
+                    (&outputs.field_2.ciphertexts[..], outputs.field_2.nonce),
+                    (&outputs.field_3.ciphertexts[..], outputs.field_3.nonce),
+                    (&outputs.field_4.ciphertexts[..], outputs.field_4.nonce),
+                ],
+                computation_offset,
+            )?
+        {
+            registry.record_failure(FAILURE_OUTPUT_SIZE);
+            ctx.accounts
+                .registration
+                .set_status(RegistrationStatus::Registered, now);
+
+            emit!(RegistrationUpdateFailedEvent {
+                user,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        }
+
+        if !updated {
+            msg!("Blind-Link: Registration update rejected; registry unchanged");
+            ctx.accounts
+                .registration
+                .set_status(RegistrationStatus::Registered, now);
+
+            emit!(RegistrationUpdateFailedEvent {
+                user,
+                reason: FAILURE_UPDATE_REJECTED,
+            });
+            return Ok(());
+        }
+        ctx.accounts.registration.store_receipt(
+            &receipt.ciphertexts,
+            receipt.nonce,
+            computation_offset,
+            now,
+        );
+
+        emit!(RegistrationUpdatedEvent {
+            registry: registry.key(),
+            user,
+        });
+
+        msg!("Blind-Link: Registration updated in Global Registry");
+        Ok(())
+    }
+
+    // ── 14. Membership Check ────────────────────────────────────────
+
+    /// Privately check that the caller's own registration landed in the
+    /// Global Registry. The circuit answers true only when the encrypted
+    /// hash is the one in the caller's Registration receipt and is present,
+    /// so the check cannot be used to look up anyone else. The answer is
+    /// encrypted to `pub_key` and stored in a MembershipSession. Charged
+    /// and paused like intersect_contacts. The registry shards are passed
+    /// as remaining accounts, in index order.
+    pub fn check_membership(
+        ctx: Context<CheckMembership>,
+        computation_offset: u64,
+        encrypted_hash: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_DISCOVERY == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            ctx.accounts.registry_state.bootstrapped,
+            ErrorCode::RegistryNotBootstrapped
+        );
+
+        let session = &mut ctx.accounts.membership_session;
+        session.bump = ctx.bumps.membership_session;
+        session.user = ctx.accounts.user.key();
+        session.computation_offset = computation_offset;
+        session.result_ciphertext = [0u8; 32];
+        session.result_nonce = [0u8; 16];
+        session.status = SessionStatus::Pending;
+        session.transition(SessionStatus::Computing)?;
+        session.created_at = Clock::get()?.unix_timestamp;
+        session.failure_reason = FAILURE_NONE;
+
+        let query_fee = ctx.accounts.fees.fee_config.query_fee;
+        ctx.accounts
+            .fees
+            .collect(&ctx.accounts.user, &ctx.accounts.system_program, query_fee)?;
+
+        // Arg 1 (Enc<Shared, ContactHash>): the queried hash
+        // Arg 2 (Enc<Mxe, RegistrationReceipt>): read from the Registration
+        // Arg 3-6 (Enc<Mxe, RegistryShard>): read from the registry shards
+        let registration = &ctx.accounts.registration;
+        let args = registry_shard_args(
+            registry_account_arg(
+                ArgBuilder::new()
+                    .x25519_pubkey(pub_key)
+                    .plaintext_u128(nonce)
+                    .encrypted_u128(encrypted_hash),
+                registration.key(),
+                &registration.encrypted_receipt,
+                registration.receipt_nonce,
+            ),
+            ctx.remaining_accounts,
+        )?
+        .build();
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![CheckMembershipCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.membership_session.key(),
+                    is_writable: true,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+
+        ctx.accounts.registry_state.computation_count += 1;
+
+        msg!(
+            "Blind-Link: Membership check queued (offset: {})",
+            computation_offset
+        );
+        Ok(())
+    }
+
+    /// Callback for check_membership: stores the encrypted membership flag
+    /// in the session. A failed verification is recorded on the session;
+    /// an outcome for a session that already settled is dropped.
+    #[arcium_callback(encrypted_ix = "check_membership")]
+    pub fn check_membership_callback(
+        ctx: Context<CheckMembershipCallback>,
+        output: SignedComputationOutputs<CheckMembershipOutput>,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.membership_session;
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Membership check verification failed: {}", e);
+                if !session.settle(SessionStatus::Failed, FAILURE_VERIFICATION) {
+                    return Ok(());
+                }
+
+                emit!(MembershipCheckFailedEvent {
+                    user: session.user,
+                    computation_offset: session.computation_offset,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
+            }
+        };
+
+        let [result_ciphertext] = verified.field_0.ciphertexts[..] else {
+            if !session.settle(SessionStatus::Failed, FAILURE_OUTPUT_SIZE) {
+                return Ok(());
+            }
+
+            emit!(MembershipCheckFailedEvent {
+                user: session.user,
+                computation_offset: session.computation_offset,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        };
+        if !session.settle(SessionStatus::Completed, FAILURE_NONE) {
+            return Ok(());
+        }
+        session.result_ciphertext = result_ciphertext;
+        session.result_nonce = verified.field_0.nonce.to_le_bytes();
+
+        emit!(MembershipCheckedEvent {
+            user: session.user,
+            computation_offset: session.computation_offset,
+        });
+
+        msg!("Blind-Link: Membership check completed");
+        Ok(())
+    }
+
+    /// Close a membership session and reclaim its rent. A late callback for
+    /// a session closed while computing fails harmlessly.
+    pub fn close_membership_session(ctx: Context<CloseMembershipSession>) -> Result<()> {
+        msg!(
+            "Blind-Link: Membership session closed (offset: {})",
+            ctx.accounts.membership_session.computation_offset
+        );
+        Ok(())
+    }
+
+    // ── 15. Hit Counts ──────────────────────────────────────────────
+
+    /// Ask how many times the caller's own entry has been matched by other
+    /// users' intersections. The caller proves ownership by submitting the
+    /// registered hash together with the ownership secret given at
+    /// registration (same key and nonce); the count, or zero for a wrong
+    /// claim, is encrypted to `pub_key` and emitted in a HitCountEvent.
+    /// Charged and paused like check_membership. The registry shards are
+    /// passed as remaining accounts, in index order. Only the latest read
+    /// or write queued for the registration reports back.
+    pub fn read_my_hit_count(
+        ctx: Context<ReadMyHitCount>,
+        computation_offset: u64,
+        encrypted_hash: [u8; 32],
+        encrypted_owner_secret: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_DISCOVERY == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            ctx.accounts.registry_state.bootstrapped,
+            ErrorCode::RegistryNotBootstrapped
+        );
+
+        let query_fee = ctx.accounts.fees.fee_config.query_fee;
+        ctx.accounts
+            .fees
+            .collect(&ctx.accounts.user, &ctx.accounts.system_program, query_fee)?;
+        // Only this read's callback may report on the registration; a later
+        // read or write supersedes it
+        ctx.accounts.registration.computation_offset = computation_offset;
+
+        // Arg 1 (Enc<Shared, RegistrationEntry>): the claimed hash and secret
+        // Arg 2-5 (Enc<Mxe, RegistryShard>): read from the registry shards
+        let args = registry_shard_args(
+            ArgBuilder::new()
+                .x25519_pubkey(pub_key)
+                .plaintext_u128(nonce)
+                .encrypted_u128(encrypted_hash)
+                .encrypted_u128(encrypted_owner_secret),
+            ctx.remaining_accounts,
+        )?
+        .build();
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![ReadMyHitCountCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.registration.key(),
+                    is_writable: false,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+
+        ctx.accounts.registry_state.computation_count += 1;
+
+        msg!(
+            "Blind-Link: Hit count read queued (offset: {})",
+            computation_offset
+        );
+        Ok(())
+    }
+
+    /// Callback for read_my_hit_count: emits the encrypted count for the
+    /// registrant to decrypt. Nothing is stored on-chain.
+    #[arcium_callback(encrypted_ix = "read_my_hit_count")]
+    pub fn read_my_hit_count_callback(
+        ctx: Context<ReadMyHitCountCallback>,
+        output: SignedComputationOutputs<ReadMyHitCountOutput>,
+    ) -> Result<()> {
+        let user = ctx.accounts.registration.user;
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Hit count verification failed: {}", e);
+                emit!(HitCountFailedEvent {
+                    user,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
+            }
+        };
+
+        let [result_ciphertext] = verified.field_0.ciphertexts[..] else {
+            emit!(HitCountFailedEvent {
+                user,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        };
+
+        emit!(HitCountEvent {
+            user,
+            result_ciphertext,
+            result_nonce: verified.field_0.nonce.to_le_bytes(),
+        });
+
+        msg!("Blind-Link: Hit count read completed");
+        Ok(())
+    }
+
+    // ── 16. Canaries ────────────────────────────────────────────────
+
+    /// Seed the registry with a canary fingerprint no real user holds, to
+    /// detect enumeration. The authority encrypts the fingerprint and a
+    /// random ownership secret client-side, like register_user; the entry
+    /// is stored and matched like any other, but every intersection that
+    /// matches a canary increments the encrypted canary hit counter (see
+    /// reveal_canary_hits). Canaries do not count towards total_users.
+    /// Whether the canary's bucket had room is returned encrypted to
+    /// `pub_key` in a CanaryInsertedEvent, so the insertion stays private.
+    /// Takes the registry write lock and the registry shards (as remaining
+    /// accounts) like register_user.
+    pub fn insert_canary(
+        ctx: Context<InsertCanary>,
+        computation_offset: u64,
+        encrypted_hash: [u8; 32],
+        encrypted_owner_secret: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.registry_state.paused & PAUSE_REGISTRATION == 0,
+            ErrorCode::RegistryPaused
+        );
+        require!(
+            ctx.accounts.registry_state.bootstrapped,
+            ErrorCode::RegistryNotBootstrapped
+        );
+        ctx.accounts
+            .registry_state
+            .acquire_write_lock(computation_offset, Clock::get()?.unix_timestamp)?;
+
+        // Arg 1 (Enc<Shared, RegistrationEntry>): the canary and its secret
+        // Arg 2 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
+        // Arg 3-6 (Enc<Mxe, RegistryShard>): read from the registry shards
+        let registry = &ctx.accounts.registry_state;
+        let registry_key = registry.key();
+        let args = registry_shard_args(
+            registry_account_arg(
+                ArgBuilder::new()
+                    .x25519_pubkey(pub_key)
+                    .plaintext_u128(nonce)
+                    .encrypted_u128(encrypted_hash)
+                    .encrypted_u128(encrypted_owner_secret),
+                registry_key,
+                &registry.encrypted_data,
+                registry.nonce,
+            ),
+            ctx.remaining_accounts,
+        )?
+        .build();
+
+        let mut callback_accounts = vec![CallbackAccount {
+            pubkey: registry_key,
+            is_writable: true,
+        }];
+        callback_accounts.extend(registry_shard_callback_accounts(ctx.remaining_accounts));
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![InsertCanaryCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &callback_accounts,
+            )?],
+            REGISTRY_WRITE_CALLBACK_TXS,
+            0,
+        )?;
+
+        msg!("Blind-Link: Canary insertion queued");
+        Ok(())
+    }
+
+    /// Callback for insert_canary: writes back the registry header and
+    /// shards. Output from a computation that lost the write lock is
+    /// discarded. A full bucket leaves the registry unchanged; the outcome
+    /// is emitted encrypted to the authority in a CanaryInsertedEvent.
+    #[arcium_callback(encrypted_ix = "insert_canary")]
+    pub fn insert_canary_callback(
+        ctx: Context<InsertCanaryCallback>,
+        output: SignedComputationOutputs<InsertCanaryOutput>,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry_state;
+        if !registry.holds_write_lock(
+            ctx.accounts.computation_account.key(),
+            &ctx.accounts.mxe_account,
+        )? {
+            msg!("Blind-Link: Canary computed against a stale registry; discarded");
+            registry.record_failure(FAILURE_STALE_WRITE);
+
+            emit!(CanaryInsertFailedEvent {
+                registry: registry.key(),
+                reason: FAILURE_STALE_WRITE,
+            });
+            return Ok(());
+        }
+        let computation_offset = registry.release_write_lock();
+
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Canary verification failed: {}", e);
+                let registry = &mut ctx.accounts.registry_state;
+                registry.record_failure(FAILURE_VERIFICATION);
+
+                emit!(CanaryInsertFailedEvent {
+                    registry: registry.key(),
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
+            }
+        };
+
+        let outputs = &verified.field_0;
+        let registry = &mut ctx.accounts.registry_state;
+        if !store_registry_output(
+            registry,
+            ctx.remaining_accounts,
+            (&outputs.field_0.ciphertexts[..], outputs.field_0.nonce),
+            &[
+                (&outputs.field_1.ciphertexts[..], outputs.field_1.nonce),
+                (&outputs.field_2.ciphertexts[..], outputs.field_2.nonce),
+                (&outputs.field_3.ciphertexts[..], outputs.field_3.nonce),
+                (&outputs.field_4.ciphertexts[..], outputs.field_4.nonce),
+            ],
+            computation_offset,
+        )? {
+            registry.record_failure(FAILURE_OUTPUT_SIZE);
+
+            emit!(CanaryInsertFailedEvent {
+                registry: registry.key(),
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        }
+
+        let [inserted_ciphertext] = outputs.field_5.ciphertexts[..] else {
+            emit!(CanaryInsertFailedEvent {
+                registry: registry.key(),
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        };
+
+        emit!(CanaryInsertedEvent {
+            registry: registry.key(),
+            inserted_ciphertext,
+            inserted_nonce: outputs.field_5.nonce.to_le_bytes(),
+        });
+
+        msg!("Blind-Link: Canary insertion settled");
+        Ok(())
+    }
+
+    /// Return the number of intersections that matched a canary, encrypted
+    /// to the authority's x25519 `pub_key` and emitted in a CanaryHitsEvent.
+    /// Authority only; nobody else can read the count.
+    pub fn reveal_canary_hits(
+        ctx: Context<RevealCanaryHits>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        // Arg 1 (Shared): the authority's key and nonce for the result
+        // Arg 2 (Enc<Mxe, GlobalRegistry>): read from on-chain registry state
+        let registry = &ctx.accounts.registry_state;
+        let args = registry_account_arg(
+            ArgBuilder::new()
+                .x25519_pubkey(pub_key)
+                .plaintext_u128(nonce),
+            registry.key(),
+            &registry.encrypted_data,
+            registry.nonce,
+        )
+        .build();
+
+        // Initialize sign PDA bump for CPI signing
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            vec![RevealCanaryHitsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.registry_state.key(),
+                    is_writable: false,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    /// Callback for reveal_canary_hits: emits the count, still encrypted to
+    /// the authority, in a CanaryHitsEvent. The registry is only read, so a
+    /// failed reveal is reported in CanaryHitsFailedEvent without touching
+    /// its failure counters.
+    #[arcium_callback(encrypted_ix = "reveal_canary_hits")]
+    pub fn reveal_canary_hits_callback(
+        ctx: Context<RevealCanaryHitsCallback>,
+        output: SignedComputationOutputs<RevealCanaryHitsOutput>,
+    ) -> Result<()> {
+        let registry = ctx.accounts.registry_state.key();
+        let verified = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(out) => out,
+            Err(e) => {
+                msg!("Blind-Link: Canary hit reveal failed: {}", e);
+                emit!(CanaryHitsFailedEvent {
+                    registry,
+                    reason: FAILURE_VERIFICATION,
+                });
+                return Ok(());
+            }
+        };
+
+        let [result_ciphertext] = verified.field_0.ciphertexts[..] else {
+            emit!(CanaryHitsFailedEvent {
+                registry,
+                reason: FAILURE_OUTPUT_SIZE,
+            });
+            return Ok(());
+        };
+
+        emit!(CanaryHitsEvent {
+            result_ciphertext,
+            result_nonce: verified.field_0.nonce.to_le_bytes(),
+        });
+
+        msg!("Blind-Link: Canary hit count revealed to authority");
+        Ok(())
+    }
+
+    // ── Comp Def Offsets ────────────────────────────────────────────────────
+
+    const COMP_DEF_OFFSET_INTERSECT_CONTACTS: u32 = comp_def_offset("intersect_contacts");
+    const COMP_DEF_OFFSET_REGISTER_USER: u32 = comp_def_offset("register_user");
+    const COMP_DEF_OFFSET_REVEAL_REGISTRY_SIZE: u32 = comp_def_offset("reveal_registry_size");
+    const COMP_DEF_OFFSET_INIT_REGISTRY: u32 = comp_def_offset("init_registry");
+    const COMP_DEF_OFFSET_PROCESS_REGISTRATION_QUEUE: u32 =
+        comp_def_offset("process_registration_queue");
+    const COMP_DEF_OFFSET_DEREGISTER_USER: u32 = comp_def_offset("deregister_user");
+    const COMP_DEF_OFFSET_UPDATE_REGISTRATION: u32 = comp_def_offset("update_registration");
+    const COMP_DEF_OFFSET_CHECK_MEMBERSHIP: u32 = comp_def_offset("check_membership");
+    const COMP_DEF_OFFSET_READ_MY_HIT_COUNT: u32 = comp_def_offset("read_my_hit_count");
+    const COMP_DEF_OFFSET_REVEAL_ANOMALY_COUNT: u32 = comp_def_offset("reveal_anomaly_count");
+    const COMP_DEF_OFFSET_INSERT_CANARY: u32 = comp_def_offset("insert_canary");
+    const COMP_DEF_OFFSET_REVEAL_CANARY_HITS: u32 = comp_def_offset("reveal_canary_hits");
+
+    // ── Account Structs ─────────────────────────────────────────────────────
+
+    #[derive(Accounts)]
+    pub struct InitializeRegistry<'info> {
+        #[account(
+        init,
+        payer = authority,
+        space = RegistryState::SPACE,
+        seeds = [REGISTRY_SEED],
+        bump
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    #[instruction(index: u8)]
+    pub struct InitializeRegistryShard<'info> {
+        #[account(
+        init,
+        payer = authority,
+        space = RegistryShard::INIT_SPACE,
+        seeds = [REGISTRY_SHARD_SEED, &[index]],
+        bump
+    )]
+        pub registry_shard: Account<'info, RegistryShard>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    pub struct CloseSession<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(
+        mut,
+        close = user,
+        seeds = [SESSION_SEED, user.key().as_ref(), &psi_session.computation_offset.to_le_bytes()],
+        bump = psi_session.bump,
+        has_one = user @ ErrorCode::Unauthorized
+    )]
+        pub psi_session: Account<'info, PsiSession>,
+    }
+
+    #[derive(Accounts)]
+    pub struct ExpireSession<'info> {
+        #[account(
+        mut,
+        seeds = [SESSION_SEED, user.key().as_ref(), &psi_session.computation_offset.to_le_bytes()],
+        bump = psi_session.bump,
+        has_one = user @ ErrorCode::Unauthorized
+    )]
+        pub psi_session: Account<'info, PsiSession>,
+        /// Session owner; receives the rent when the session is closed.
+        /// Need not sign.
+        #[account(mut)]
+        pub user: SystemAccount<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+    }
+
+    #[derive(Accounts)]
+    pub struct CancelSession<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(
+        mut,
+        close = user,
+        seeds = [SESSION_SEED, user.key().as_ref(), &psi_session.computation_offset.to_le_bytes()],
+        bump = psi_session.bump,
+        has_one = user @ ErrorCode::Unauthorized
+    )]
+        pub psi_session: Account<'info, PsiSession>,
+        #[account(seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+    }
+
+    #[derive(Accounts)]
+    pub struct RegistryAdmin<'info> {
+        #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        pub authority: Signer<'info>,
+    }
+
+    #[derive(Accounts)]
+    pub struct AcceptAuthority<'info> {
+        #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        constraint = registry_state.pending_authority == Some(new_authority.key())
+            @ ErrorCode::NotPendingAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        pub new_authority: Signer<'info>,
+    }
+
+    #[derive(Accounts)]
+    pub struct ResizeRegistry<'info> {
+        #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority,
+        realloc = resize_step(registry_state.to_account_info().data_len(), RegistryState::SPACE),
+        realloc::payer = authority,
+        realloc::zero = false
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    #[instruction(index: u8)]
+    pub struct ResizeRegistryShard<'info> {
+        #[account(
+        mut,
+        seeds = [REGISTRY_SHARD_SEED, &[index]],
+        bump = registry_shard.bump,
+        realloc = resize_step(registry_shard.to_account_info().data_len(), RegistryShard::SPACE),
+        realloc::payer = authority,
+        realloc::zero = false
+    )]
+        pub registry_shard: Account<'info, RegistryShard>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    #[instruction(index: u8)]
+    pub struct CloseRegistryShard<'info> {
+        #[account(
+        mut,
+        close = authority,
+        seeds = [REGISTRY_SHARD_SEED, &[index]],
+        bump = registry_shard.bump
+    )]
+        pub registry_shard: Account<'info, RegistryShard>,
+        #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+    }
+
+    #[derive(Accounts)]
+    pub struct CloseRegistry<'info> {
+        #[account(
+        mut,
+        close = authority,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        /// CHECK: May not exist; when it does, close_registry deserializes it
+        /// to check it is empty.
+        #[account(seeds = [REGISTRATION_QUEUE_SEED], bump)]
+        pub registration_queue: UncheckedAccount<'info>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+    }
+
+    // ── Init Computation Definition Accounts ────────────────────────────────
+
+    #[init_computation_definition_accounts("intersect_contacts", payer)]
+    #[derive(Accounts)]
+    pub struct InitIntersectContactsCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[init_computation_definition_accounts("register_user", payer)]
+    #[derive(Accounts)]
+    pub struct InitRegisterUserCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[init_computation_definition_accounts("reveal_registry_size", payer)]
+    #[derive(Accounts)]
+    pub struct InitRevealRegistrySizeCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[init_computation_definition_accounts("reveal_anomaly_count", payer)]
+    #[derive(Accounts)]
+    pub struct InitRevealAnomalyCountCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    // ── Queue Computation Accounts ──────────────────────────────────────────
+
+    #[queue_computation_accounts("intersect_contacts", user)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct IntersectContacts<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(
+        init,
+        payer = user,
+        space = PsiSession::SPACE,
+        seeds = [SESSION_SEED, user.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+        pub psi_session: Account<'info, PsiSession>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INTERSECT_CONTACTS))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub fees: CollectFee<'info>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    #[queue_computation_accounts("intersect_contacts", user)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct RetryIntersect<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(
+        mut,
+        seeds = [SESSION_SEED, user.key().as_ref(), &psi_session.computation_offset.to_le_bytes()],
+        bump = psi_session.bump,
+        has_one = user @ ErrorCode::Unauthorized
+    )]
+        pub psi_session: Account<'info, PsiSession>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INTERSECT_CONTACTS))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub fees: CollectFee<'info>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    #[queue_computation_accounts("register_user", user)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct RegisterUser<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        seeds = [ESCROW_SEED, user.key().as_ref()],
+        bump = registration_escrow.bump,
+        constraint = registration_escrow.stake_amount >= fees.fee_config.registration_stake
+            @ ErrorCode::InsufficientStake
+    )]
+        pub registration_escrow: Account<'info, RegistrationEscrow>,
+        #[account(
+        init,
+        payer = user,
+        space = Registration::SPACE,
+        seeds = [REGISTRATION_SEED, user.key().as_ref()],
+        bump,
+    )]
+        pub registration: Account<'info, Registration>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REGISTER_USER))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub fees: CollectFee<'info>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    #[queue_computation_accounts("reveal_registry_size", payer)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct RevealRegistrySize<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_REGISTRY_SIZE))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    #[queue_computation_accounts("reveal_anomaly_count", authority)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct RevealAnomalyCount<'info> {
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_ANOMALY_COUNT))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    // ── Callback Accounts ───────────────────────────────────────────────────
+
+    #[callback_accounts("intersect_contacts")]
+    #[derive(Accounts)]
+    pub struct IntersectContactsCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INTERSECT_CONTACTS))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        /// Must be the canonical session PDA whose active computation is the
+        /// one being finalized; stale callbacks from superseded attempts fail.
+        #[account(
+        mut,
+        seeds = [SESSION_SEED, psi_session.user.as_ref(), &psi_session.computation_offset.to_le_bytes()],
+        bump = psi_session.bump,
+        constraint = computation_account.key()
+            == derive_comp_pda!(psi_session.active_computation_offset, mxe_account, ErrorCode::ClusterNotSet)
+            @ ErrorCode::CallbackAccountMismatch
+    )]
+        pub psi_session: Account<'info, PsiSession>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+    }
+
+    #[callback_accounts("register_user")]
+    #[derive(Accounts)]
+    pub struct RegisterUserCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REGISTER_USER))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        /// Must be the registration whose computation is being finalized
+        #[account(
+        mut,
+        seeds = [REGISTRATION_SEED, registration.user.as_ref()],
+        bump = registration.bump,
+        constraint = computation_account.key()
+            == derive_comp_pda!(registration.computation_offset, mxe_account, ErrorCode::ClusterNotSet)
+            @ ErrorCode::CallbackAccountMismatch
+    )]
+        pub registration: Account<'info, Registration>,
+        /// Receives the Registration's rent if the registration fails
+        #[account(mut, address = registration.user)]
+        pub user: SystemAccount<'info>,
+    }
+
+    #[callback_accounts("reveal_registry_size")]
+    #[derive(Accounts)]
+    pub struct RevealRegistrySizeCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_REGISTRY_SIZE))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        /// CHECK: Payer of the reveal, named in failure events
+        pub payer: UncheckedAccount<'info>,
+    }
+
+    #[callback_accounts("reveal_anomaly_count")]
+    #[derive(Accounts)]
+    pub struct RevealAnomalyCountCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_ANOMALY_COUNT))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        #[account(seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+    }
+
+    #[init_computation_definition_accounts("init_registry", payer)]
+    #[derive(Accounts)]
+    pub struct InitInitRegistryCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[queue_computation_accounts("init_registry", payer)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct QueueInitRegistry<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        constraint = registry_state.authority == payer.key() @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_REGISTRY))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    #[callback_accounts("init_registry")]
+    #[derive(Accounts)]
+    pub struct InitRegistryCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_REGISTRY))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+    }
+
+    // ── Registration Queue Accounts ─────────────────────────────────────────
+
+    #[derive(Accounts)]
+    pub struct InitializeRegistrationQueue<'info> {
+        #[account(
+        init,
+        payer = authority,
+        space = RegistrationQueue::SPACE,
+        seeds = [REGISTRATION_QUEUE_SEED],
+        bump
+    )]
+        pub registration_queue: Account<'info, RegistrationQueue>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    pub struct EnqueueRegistration<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(mut, seeds = [REGISTRATION_QUEUE_SEED], bump = registration_queue.bump)]
+        pub registration_queue: Account<'info, RegistrationQueue>,
+        #[account(seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        seeds = [ESCROW_SEED, user.key().as_ref()],
+        bump = registration_escrow.bump,
+        constraint = registration_escrow.stake_amount >= fees.fee_config.registration_stake
+            @ ErrorCode::InsufficientStake
+    )]
+        pub registration_escrow: Account<'info, RegistrationEscrow>,
+        #[account(
+        init,
+        payer = user,
+        space = Registration::SPACE,
+        seeds = [REGISTRATION_SEED, user.key().as_ref()],
+        bump,
+    )]
+        pub registration: Account<'info, Registration>,
+        pub fees: CollectFee<'info>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[init_computation_definition_accounts("process_registration_queue", payer)]
+    #[derive(Accounts)]
+    pub struct InitProcessRegistrationQueueCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[queue_computation_accounts("process_registration_queue", payer)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct ProcessRegistrationQueue<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut, seeds = [REGISTRATION_QUEUE_SEED], bump = registration_queue.bump)]
+        pub registration_queue: Account<'info, RegistrationQueue>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PROCESS_REGISTRATION_QUEUE))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    #[callback_accounts("process_registration_queue")]
+    #[derive(Accounts)]
+    pub struct ProcessRegistrationQueueCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PROCESS_REGISTRATION_QUEUE))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut, seeds = [REGISTRATION_QUEUE_SEED], bump = registration_queue.bump)]
+        pub registration_queue: Account<'info, RegistrationQueue>,
+    }
+
+    // ── Fee Accounts ────────────────────────────────────────────────────────
+
+    /// Fee accounts embedded in every paid instruction. The token accounts
+    /// are only required while the config charges an SPL mint.
+    #[derive(Accounts)]
+    pub struct CollectFee<'info> {
+        #[account(mut, seeds = [FEE_CONFIG_SEED], bump = fee_config.bump)]
+        pub fee_config: Box<Account<'info, FeeConfig>>,
+        #[account(mut, seeds = [TREASURY_SEED], bump = fee_config.treasury_bump)]
+        pub treasury: SystemAccount<'info>,
+        /// Payer's token account for `fee_config.fee_mint`
+        #[account(mut)]
+        pub payer_token_account: Option<Box<Account<'info, TokenAccount>>>,
+        #[account(
+        mut,
+        seeds = [TREASURY_SEED, treasury_token_account.mint.as_ref()],
+        bump,
+        constraint = fee_config.fee_mint == Some(treasury_token_account.mint)
+            @ ErrorCode::FeeMintMismatch
+    )]
+        pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
+        pub token_program: Option<Program<'info, Token>>,
+    }
+
+    impl<'info> CollectFee<'info> {
+        /// Move `amount` from `payer` into the treasury, in lamports or in
+        /// the configured mint.
+        fn collect(
+            &mut self,
+            payer: &Signer<'info>,
+            system_program: &Program<'info, System>,
+            amount: u64,
+        ) -> Result<()> {
+            if amount == 0 {
+                return Ok(());
+            }
+
+            let config = &mut self.fee_config;
+            match config.fee_mint {
+                None => {
+                    system_program::transfer(
+                        CpiContext::new(
+                            system_program.to_account_info(),
+                            system_program::Transfer {
+                                from: payer.to_account_info(),
+                                to: self.treasury.to_account_info(),
+                            },
+                        ),
+                        amount,
+                    )?;
+                    config.lamports_collected = config.lamports_collected.saturating_add(amount);
+                }
+                Some(_) => {
+                    let (Some(from), Some(to), Some(token_program)) = (
+                        &self.payer_token_account,
+                        &self.treasury_token_account,
+                        &self.token_program,
+                    ) else {
+                        return err!(ErrorCode::FeeTokenAccountsMissing);
+                    };
+                    token::transfer(
+                        CpiContext::new(
+                            token_program.to_account_info(),
+                            token::Transfer {
+                                from: from.to_account_info(),
+                                to: to.to_account_info(),
+                                authority: payer.to_account_info(),
+                            },
+                        ),
+                        amount,
+                    )?;
+                    config.tokens_collected = config.tokens_collected.saturating_add(amount);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Accounts)]
+    pub struct InitializeFeeConfig<'info> {
+        #[account(
+        init,
+        payer = authority,
+        space = FeeConfig::SPACE,
+        seeds = [FEE_CONFIG_SEED],
+        bump
+    )]
+        pub fee_config: Account<'info, FeeConfig>,
+        #[account(mut, seeds = [TREASURY_SEED], bump)]
+        pub treasury: SystemAccount<'info>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    pub struct FeeAdmin<'info> {
+        #[account(mut, seeds = [FEE_CONFIG_SEED], bump = fee_config.bump)]
+        pub fee_config: Account<'info, FeeConfig>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        pub authority: Signer<'info>,
+    }
+
+    #[derive(Accounts)]
+    pub struct SetFeeMint<'info> {
+        #[account(mut, seeds = [FEE_CONFIG_SEED], bump = fee_config.bump)]
+        pub fee_config: Account<'info, FeeConfig>,
+        #[account(seeds = [TREASURY_SEED], bump = fee_config.treasury_bump)]
+        pub treasury: SystemAccount<'info>,
+        pub fee_mint: Account<'info, Mint>,
+        #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [TREASURY_SEED, fee_mint.key().as_ref()],
+        bump,
+        token::mint = fee_mint,
+        token::authority = treasury
+    )]
+        pub treasury_token_account: Account<'info, TokenAccount>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        pub token_program: Program<'info, Token>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    pub struct WithdrawFees<'info> {
+        #[account(seeds = [FEE_CONFIG_SEED], bump = fee_config.bump)]
+        pub fee_config: Account<'info, FeeConfig>,
+        #[account(mut, seeds = [TREASURY_SEED], bump = fee_config.treasury_bump)]
+        pub treasury: SystemAccount<'info>,
+        #[account(
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
+        pub registry_state: Account<'info, RegistryState>,
+        pub authority: Signer<'info>,
+        /// Receives withdrawn lamports
+        #[account(mut)]
+        pub destination: SystemAccount<'info>,
+        #[account(
+        mut,
+        seeds = [TREASURY_SEED, treasury_token_account.mint.as_ref()],
+        bump
+    )]
+        pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+        /// Receives withdrawn tokens
+        #[account(mut)]
+        pub destination_token_account: Option<Account<'info, TokenAccount>>,
+        pub token_program: Option<Program<'info, Token>>,
+        pub system_program: Program<'info, System>,
+    }
+
+    // ── Registration Stake Accounts ─────────────────────────────────────────
+
+    #[derive(Accounts)]
+    pub struct DepositStake<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(seeds = [FEE_CONFIG_SEED], bump = fee_config.bump)]
+        pub fee_config: Account<'info, FeeConfig>,
+        #[account(
+        init,
+        payer = user,
+        space = RegistrationEscrow::SPACE,
+        seeds = [ESCROW_SEED, user.key().as_ref()],
+        bump
+    )]
+        pub registration_escrow: Account<'info, RegistrationEscrow>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[derive(Accounts)]
+    pub struct DepositStakeTokens<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(
+        seeds = [FEE_CONFIG_SEED],
+        bump = fee_config.bump,
+        constraint = fee_config.fee_mint == Some(stake_mint.key()) @ ErrorCode::StakeMintMismatch
+    )]
+        pub fee_config: Account<'info, FeeConfig>,
+        pub stake_mint: Account<'info, Mint>,
+        #[account(
+        init,
+        payer = user,
+        space = RegistrationEscrow::SPACE,
+        seeds = [ESCROW_SEED, user.key().as_ref()],
+        bump
+    )]
+        pub registration_escrow: Account<'info, RegistrationEscrow>,
+        #[account(
+        init,
+        payer = user,
+        seeds = [ESCROW_VAULT_SEED, user.key().as_ref()],
+        bump,
+        token::mint = stake_mint,
+        token::authority = registration_escrow
+    )]
+        pub escrow_vault: Account<'info, TokenAccount>,
+        #[account(mut)]
+        pub user_token_account: Account<'info, TokenAccount>,
+        pub token_program: Program<'info, Token>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[init_computation_definition_accounts("deregister_user", payer)]
+    #[derive(Accounts)]
+    pub struct InitDeregisterUserCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[queue_computation_accounts("deregister_user", payer)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct DeregisterUser<'info> {
+        /// The registrant, or the registry authority
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        mut,
+        seeds = [REGISTRATION_SEED, registration.user.as_ref()],
+        bump = registration.bump,
+        constraint = payer.key() == registration.user
+            || payer.key() == registry_state.authority @ ErrorCode::Unauthorized,
+        constraint = registration.status == RegistrationStatus::Registered
+            @ ErrorCode::NotRegistered
+    )]
+        pub registration: Account<'info, Registration>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_DEREGISTER_USER))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
+    }
+
+    #[callback_accounts("deregister_user")]
+    #[derive(Accounts)]
+    pub struct DeregisterUserCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_DEREGISTER_USER))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        /// Must be the registration whose computation is being finalized
+        #[account(
+        mut,
+        seeds = [REGISTRATION_SEED, registration.user.as_ref()],
+        bump = registration.bump,
+        constraint = computation_account.key()
+            == derive_comp_pda!(registration.computation_offset, mxe_account, ErrorCode::ClusterNotSet)
+            @ ErrorCode::CallbackAccountMismatch
+    )]
+        pub registration: Account<'info, Registration>,
+        /// Receives the registration's rent once it is removed
+        #[account(mut, address = registration.user)]
+        pub user: SystemAccount<'info>,
+    }
+
+    #[derive(Accounts)]
+    pub struct RecoverRegistration<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        mut,
+        seeds = [REGISTRATION_SEED, user.key().as_ref()],
+        bump = registration.bump
+    )]
+        pub registration: Account<'info, Registration>,
+    }
 
-    // ── Account Structs ─────────────────────────────────────────────────────
+    #[derive(Accounts)]
+    pub struct WithdrawStake<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(
+        mut,
+        seeds = [ESCROW_SEED, user.key().as_ref()],
+        bump = registration_escrow.bump,
+        close = user
+    )]
+        pub registration_escrow: Account<'info, RegistrationEscrow>,
+        /// CHECK: the wallet's Registration PDA, which must not exist
+        #[account(
+        seeds = [REGISTRATION_SEED, user.key().as_ref()],
+        bump,
+        constraint = registration.data_is_empty() @ ErrorCode::StakeLocked
+    )]
+        pub registration: UncheckedAccount<'info>,
+        #[account(mut, seeds = [ESCROW_VAULT_SEED, user.key().as_ref()], bump)]
+        pub escrow_vault: Option<Account<'info, TokenAccount>>,
+        /// Receives a token stake
+        #[account(mut)]
+        pub user_token_account: Option<Account<'info, TokenAccount>>,
+        pub token_program: Option<Program<'info, Token>>,
+    }
 
     #[derive(Accounts)]
-    pub struct InitializeRegistry<'info> {
+    pub struct SlashStake<'info> {
+        #[account(seeds = [FEE_CONFIG_SEED], bump = fee_config.bump)]
+        pub fee_config: Account<'info, FeeConfig>,
+        #[account(mut, seeds = [TREASURY_SEED], bump = fee_config.treasury_bump)]
+        pub treasury: SystemAccount<'info>,
         #[account(
-        init,
-        payer = authority,
-        space = 10240,
         seeds = [REGISTRY_SEED],
-        bump
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
     )]
         pub registry_state: Account<'info, RegistryState>,
-        #[account(mut)]
         pub authority: Signer<'info>,
-        pub system_program: Program<'info, System>,
+        #[account(
+        mut,
+        seeds = [ESCROW_SEED, registration_escrow.user.as_ref()],
+        bump = registration_escrow.bump
+    )]
+        pub registration_escrow: Account<'info, RegistrationEscrow>,
+        #[account(
+        mut,
+        seeds = [ESCROW_VAULT_SEED, registration_escrow.user.as_ref()],
+        bump
+    )]
+        pub escrow_vault: Option<Account<'info, TokenAccount>>,
+        #[account(
+        mut,
+        seeds = [TREASURY_SEED, treasury_token_account.mint.as_ref()],
+        bump
+    )]
+        pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+        pub token_program: Option<Program<'info, Token>>,
     }
 
-    // ── Init Computation Definition Accounts ────────────────────────────────
-
-    #[init_computation_definition_accounts("intersect_contacts", payer)]
+    #[init_computation_definition_accounts("update_registration", payer)]
     #[derive(Accounts)]
-    pub struct InitIntersectContactsCompDef<'info> {
+    pub struct InitUpdateRegistrationCompDef<'info> {
         #[account(mut)]
         pub payer: Signer<'info>,
         #[account(mut, address = derive_mxe_pda!())]
@@ -512,29 +5600,90 @@ pub mod blind_link {
         pub system_program: Program<'info, System>,
     }
 
-    #[init_computation_definition_accounts("register_user", payer)]
+    #[queue_computation_accounts("update_registration", user)]
     #[derive(Accounts)]
-    pub struct InitRegisterUserCompDef<'info> {
+    #[instruction(computation_offset: u64)]
+    pub struct UpdateRegistration<'info> {
         #[account(mut)]
-        pub payer: Signer<'info>,
-        #[account(mut, address = derive_mxe_pda!())]
+        pub user: Signer<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        #[account(
+        mut,
+        seeds = [REGISTRATION_SEED, user.key().as_ref()],
+        bump = registration.bump,
+        constraint = registration.status == RegistrationStatus::Registered
+            @ ErrorCode::NotRegistered
+    )]
+        pub registration: Account<'info, Registration>,
+        #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+        pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+        #[account(address = derive_mxe_pda!())]
         pub mxe_account: Box<Account<'info, MXEAccount>>,
-        #[account(mut)]
-        /// CHECK: comp_def_account, checked by arcium program.
-        pub comp_def_account: UncheckedAccount<'info>,
-        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
-        /// CHECK: address_lookup_table, checked by arcium program.
-        pub address_lookup_table: UncheckedAccount<'info>,
-        #[account(address = LUT_PROGRAM_ID)]
-        /// CHECK: lut_program is the Address Lookup Table program.
-        pub lut_program: UncheckedAccount<'info>,
-        pub arcium_program: Program<'info, Arcium>,
+        #[account(mut, address = derive_mempool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: mempool_account, checked by arcium program.
+        pub mempool_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_execpool_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: executing_pool, checked by arcium program.
+        pub executing_pool: UncheckedAccount<'info>,
+        #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
+        /// CHECK: computation_account, checked by arcium program.
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_UPDATE_REGISTRATION))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+        pub pool_account: Account<'info, FeePool>,
+        #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+        pub clock_account: Account<'info, ClockAccount>,
+        pub fees: CollectFee<'info>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
         pub system_program: Program<'info, System>,
+        pub arcium_program: Program<'info, Arcium>,
     }
 
-    #[init_computation_definition_accounts("reveal_registry_size", payer)]
+    #[callback_accounts("update_registration")]
     #[derive(Accounts)]
-    pub struct InitRevealRegistrySizeCompDef<'info> {
+    pub struct UpdateRegistrationCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_UPDATE_REGISTRATION))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub registry_state: Account<'info, RegistryState>,
+        /// Must be the registration whose computation is being finalized
+        #[account(
+        mut,
+        seeds = [REGISTRATION_SEED, registration.user.as_ref()],
+        bump = registration.bump,
+        constraint = computation_account.key()
+            == derive_comp_pda!(registration.computation_offset, mxe_account, ErrorCode::ClusterNotSet)
+            @ ErrorCode::CallbackAccountMismatch
+    )]
+        pub registration: Account<'info, Registration>,
+    }
+
+    #[init_computation_definition_accounts("check_membership", payer)]
+    #[derive(Accounts)]
+    pub struct InitCheckMembershipCompDef<'info> {
         #[account(mut)]
         pub payer: Signer<'info>,
         #[account(mut, address = derive_mxe_pda!())]
@@ -552,24 +5701,30 @@ pub mod blind_link {
         pub system_program: Program<'info, System>,
     }
 
-    // ── Queue Computation Accounts ──────────────────────────────────────────
-
-    #[queue_computation_accounts("intersect_contacts", user)]
+    #[queue_computation_accounts("check_membership", user)]
     #[derive(Accounts)]
     #[instruction(computation_offset: u64)]
-    pub struct IntersectContacts<'info> {
+    pub struct CheckMembership<'info> {
         #[account(mut)]
         pub user: Signer<'info>,
         #[account(
         init,
         payer = user,
-        space = 8 + 1 + 32 + 8 + 4 + 16 + 1 + 8 + 2048,
-        seeds = [SESSION_SEED, user.key().as_ref(), &computation_offset.to_le_bytes()],
+        space = MembershipSession::SPACE,
+        seeds = [MEMBERSHIP_SEED, user.key().as_ref(), &computation_offset.to_le_bytes()],
         bump
     )]
-        pub psi_session: Account<'info, PsiSession>,
+        pub membership_session: Account<'info, MembershipSession>,
         #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
         pub registry_state: Account<'info, RegistryState>,
+        /// The caller's registration, whose receipt bounds the lookup
+        #[account(
+        seeds = [REGISTRATION_SEED, user.key().as_ref()],
+        bump = registration.bump,
+        constraint = registration.status == RegistrationStatus::Registered
+            @ ErrorCode::NotRegistered
+    )]
+        pub registration: Account<'info, Registration>,
         #[account(
         init_if_needed,
         space = 9,
@@ -590,7 +5745,7 @@ pub mod blind_link {
         #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
         /// CHECK: computation_account, checked by arcium program.
         pub computation_account: UncheckedAccount<'info>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INTERSECT_CONTACTS))]
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_MEMBERSHIP))]
         pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
         #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
         pub cluster_account: Account<'info, Cluster>,
@@ -598,16 +5753,87 @@ pub mod blind_link {
         pub pool_account: Account<'info, FeePool>,
         #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
         pub clock_account: Account<'info, ClockAccount>,
+        pub fees: CollectFee<'info>,
         pub system_program: Program<'info, System>,
         pub arcium_program: Program<'info, Arcium>,
     }
 
-    #[queue_computation_accounts("register_user", user)]
+    #[callback_accounts("check_membership")]
+    #[derive(Accounts)]
+    pub struct CheckMembershipCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_MEMBERSHIP))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        /// Must be the session whose computation is being finalized
+        #[account(
+        mut,
+        seeds = [MEMBERSHIP_SEED, membership_session.user.as_ref(), &membership_session.computation_offset.to_le_bytes()],
+        bump = membership_session.bump,
+        constraint = computation_account.key()
+            == derive_comp_pda!(membership_session.computation_offset, mxe_account, ErrorCode::ClusterNotSet)
+            @ ErrorCode::CallbackAccountMismatch
+    )]
+        pub membership_session: Account<'info, MembershipSession>,
+    }
+
+    #[derive(Accounts)]
+    pub struct CloseMembershipSession<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        #[account(
+        mut,
+        close = user,
+        seeds = [MEMBERSHIP_SEED, user.key().as_ref(), &membership_session.computation_offset.to_le_bytes()],
+        bump = membership_session.bump,
+        has_one = user @ ErrorCode::Unauthorized
+    )]
+        pub membership_session: Account<'info, MembershipSession>,
+    }
+
+    #[init_computation_definition_accounts("read_my_hit_count", payer)]
+    #[derive(Accounts)]
+    pub struct InitReadMyHitCountCompDef<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[queue_computation_accounts("read_my_hit_count", user)]
     #[derive(Accounts)]
     #[instruction(computation_offset: u64)]
-    pub struct RegisterUser<'info> {
+    pub struct ReadMyHitCount<'info> {
         #[account(mut)]
         pub user: Signer<'info>,
+        /// The caller's Registration; records the read's offset, and the
+        /// callback reports to its owner
+        #[account(
+        mut,
+        seeds = [REGISTRATION_SEED, user.key().as_ref()],
+        bump = registration.bump,
+        constraint = registration.status == RegistrationStatus::Registered @ ErrorCode::NotRegistered
+    )]
+        pub registration: Account<'info, Registration>,
         #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
         pub registry_state: Account<'info, RegistryState>,
         #[account(
@@ -630,7 +5856,7 @@ pub mod blind_link {
         #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
         /// CHECK: computation_account, checked by arcium program.
         pub computation_account: UncheckedAccount<'info>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REGISTER_USER))]
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_READ_MY_HIT_COUNT))]
         pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
         #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
         pub cluster_account: Account<'info, Cluster>,
@@ -638,22 +5864,74 @@ pub mod blind_link {
         pub pool_account: Account<'info, FeePool>,
         #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
         pub clock_account: Account<'info, ClockAccount>,
+        pub fees: CollectFee<'info>,
         pub system_program: Program<'info, System>,
         pub arcium_program: Program<'info, Arcium>,
     }
 
-    #[queue_computation_accounts("reveal_registry_size", payer)]
+    #[callback_accounts("read_my_hit_count")]
     #[derive(Accounts)]
-    #[instruction(computation_offset: u64)]
-    pub struct RevealRegistrySize<'info> {
+    pub struct ReadMyHitCountCallback<'info> {
+        pub arcium_program: Program<'info, Arcium>,
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_READ_MY_HIT_COUNT))]
+        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+        #[account(address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
+        pub computation_account: UncheckedAccount<'info>,
+        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
+        pub cluster_account: Account<'info, Cluster>,
+        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
+        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+        pub instructions_sysvar: AccountInfo<'info>,
+        /// Must be the registration whose computation is being finalized
+        #[account(
+        seeds = [REGISTRATION_SEED, registration.user.as_ref()],
+        bump = registration.bump,
+        constraint = computation_account.key()
+            == derive_comp_pda!(registration.computation_offset, mxe_account, ErrorCode::ClusterNotSet)
+            @ ErrorCode::CallbackAccountMismatch
+    )]
+        pub registration: Account<'info, Registration>,
+    }
+
+    #[init_computation_definition_accounts("insert_canary", payer)]
+    #[derive(Accounts)]
+    pub struct InitInsertCanaryCompDef<'info> {
         #[account(mut)]
         pub payer: Signer<'info>,
-        #[account(seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        #[account(mut, address = derive_mxe_pda!())]
+        pub mxe_account: Box<Account<'info, MXEAccount>>,
+        #[account(mut)]
+        /// CHECK: comp_def_account, checked by arcium program.
+        pub comp_def_account: UncheckedAccount<'info>,
+        #[account(mut, address = derive_mxe_lut_pda!(mxe_account.lut_offset_slot))]
+        /// CHECK: address_lookup_table, checked by arcium program.
+        pub address_lookup_table: UncheckedAccount<'info>,
+        #[account(address = LUT_PROGRAM_ID)]
+        /// CHECK: lut_program is the Address Lookup Table program.
+        pub lut_program: UncheckedAccount<'info>,
+        pub arcium_program: Program<'info, Arcium>,
+        pub system_program: Program<'info, System>,
+    }
+
+    #[queue_computation_accounts("insert_canary", authority)]
+    #[derive(Accounts)]
+    #[instruction(computation_offset: u64)]
+    pub struct InsertCanary<'info> {
+        #[account(mut)]
+        pub authority: Signer<'info>,
+        #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
         pub registry_state: Account<'info, RegistryState>,
         #[account(
         init_if_needed,
         space = 9,
-        payer = payer,
+        payer = authority,
         seeds = [&SIGN_PDA_SEED],
         bump,
         address = derive_sign_pda!(),
@@ -670,7 +5948,7 @@ pub mod blind_link {
         #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
         /// CHECK: computation_account, checked by arcium program.
         pub computation_account: UncheckedAccount<'info>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_REGISTRY_SIZE))]
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INSERT_CANARY))]
         pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
         #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
         pub cluster_account: Account<'info, Cluster>,
@@ -682,32 +5960,11 @@ pub mod blind_link {
         pub arcium_program: Program<'info, Arcium>,
     }
 
-    // ── Callback Accounts ───────────────────────────────────────────────────
-
-    #[callback_accounts("intersect_contacts")]
-    #[derive(Accounts)]
-    pub struct IntersectContactsCallback<'info> {
-        pub arcium_program: Program<'info, Arcium>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INTERSECT_CONTACTS))]
-        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
-        #[account(address = derive_mxe_pda!())]
-        pub mxe_account: Box<Account<'info, MXEAccount>>,
-        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
-        pub computation_account: UncheckedAccount<'info>,
-        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
-        pub cluster_account: Account<'info, Cluster>,
-        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
-        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
-        pub instructions_sysvar: AccountInfo<'info>,
-        #[account(mut)]
-        pub psi_session: Account<'info, PsiSession>,
-    }
-
-    #[callback_accounts("register_user")]
+    #[callback_accounts("insert_canary")]
     #[derive(Accounts)]
-    pub struct RegisterUserCallback<'info> {
+    pub struct InsertCanaryCallback<'info> {
         pub arcium_program: Program<'info, Arcium>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REGISTER_USER))]
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INSERT_CANARY))]
         pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
         #[account(address = derive_mxe_pda!())]
         pub mxe_account: Box<Account<'info, MXEAccount>>,
@@ -718,30 +5975,13 @@ pub mod blind_link {
         /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
         #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
         pub instructions_sysvar: AccountInfo<'info>,
-        #[account(mut)]
+        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
         pub registry_state: Account<'info, RegistryState>,
     }
 
-    #[callback_accounts("reveal_registry_size")]
-    #[derive(Accounts)]
-    pub struct RevealRegistrySizeCallback<'info> {
-        pub arcium_program: Program<'info, Arcium>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_REGISTRY_SIZE))]
-        pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
-        #[account(address = derive_mxe_pda!())]
-        pub mxe_account: Box<Account<'info, MXEAccount>>,
-        /// CHECK: Verified by Arcium callback handler via SignedComputationOutputs
-        pub computation_account: UncheckedAccount<'info>,
-        #[account(address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
-        pub cluster_account: Account<'info, Cluster>,
-        /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
-        #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
-        pub instructions_sysvar: AccountInfo<'info>,
-    }
-
-    #[init_computation_definition_accounts("init_registry", payer)]
+    #[init_computation_definition_accounts("reveal_canary_hits", payer)]
     #[derive(Accounts)]
-    pub struct InitInitRegistryCompDef<'info> {
+    pub struct InitRevealCanaryHitsCompDef<'info> {
         #[account(mut)]
         pub payer: Signer<'info>,
         #[account(mut, address = derive_mxe_pda!())]
@@ -759,18 +5999,23 @@ pub mod blind_link {
         pub system_program: Program<'info, System>,
     }
 
-    #[queue_computation_accounts("init_registry", payer)]
+    #[queue_computation_accounts("reveal_canary_hits", authority)]
     #[derive(Accounts)]
     #[instruction(computation_offset: u64)]
-    pub struct QueueInitRegistry<'info> {
+    pub struct RevealCanaryHits<'info> {
         #[account(mut)]
-        pub payer: Signer<'info>,
-        #[account(mut, seeds = [REGISTRY_SEED], bump = registry_state.bump)]
+        pub authority: Signer<'info>,
+        #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_state.bump,
+        has_one = authority @ ErrorCode::NotRegistryAuthority
+    )]
         pub registry_state: Account<'info, RegistryState>,
         #[account(
         init_if_needed,
         space = 9,
-        payer = payer,
+        payer = authority,
         seeds = [&SIGN_PDA_SEED],
         bump,
         address = derive_sign_pda!(),
@@ -787,7 +6032,7 @@ pub mod blind_link {
         #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, ErrorCode::ClusterNotSet))]
         /// CHECK: computation_account, checked by arcium program.
         pub computation_account: UncheckedAccount<'info>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_REGISTRY))]
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_CANARY_HITS))]
         pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
         #[account(mut, address = derive_cluster_pda!(mxe_account, ErrorCode::ClusterNotSet))]
         pub cluster_account: Account<'info, Cluster>,
@@ -799,11 +6044,11 @@ pub mod blind_link {
         pub arcium_program: Program<'info, Arcium>,
     }
 
-    #[callback_accounts("init_registry")]
+    #[callback_accounts("reveal_canary_hits")]
     #[derive(Accounts)]
-    pub struct InitRegistryCallback<'info> {
+    pub struct RevealCanaryHitsCallback<'info> {
         pub arcium_program: Program<'info, Arcium>,
-        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_REGISTRY))]
+        #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_CANARY_HITS))]
         pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
         #[account(address = derive_mxe_pda!())]
         pub mxe_account: Box<Account<'info, MXEAccount>>,
@@ -814,7 +6059,7 @@ pub mod blind_link {
         /// CHECK: Validated by address constraint matching Solana instructions sysvar ID
         #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
         pub instructions_sysvar: AccountInfo<'info>,
-        #[account(mut)]
+        #[account(seeds = [REGISTRY_SEED], bump = registry_state.bump)]
         pub registry_state: Account<'info, RegistryState>,
     }
 
@@ -826,6 +6071,8 @@ pub mod blind_link {
         pub computation_offset: u64,
         pub result_ciphertexts: Vec<[u8; 32]>,
         pub result_nonce: [u8; 16],
+        /// Registry version the intersection was queued against
+        pub registry_version: u64,
     }
 
     /// Emitted when a user is successfully registered.
@@ -836,24 +6083,777 @@ pub mod blind_link {
         pub registry: Pubkey,
     }
 
+    #[event]
+    pub struct RegistrationEnqueuedEvent {
+        pub user: Pubkey,
+        /// Zero-based position in the queue at enqueue time
+        pub position: u32,
+    }
+
+    #[event]
+    pub struct RegistrationBatchProcessedEvent {
+        pub registry: Pubkey,
+        pub processed: u8,
+        pub remaining: u32,
+    }
+
+    /// One entry of the registry transparency log, emitted whenever a
+    /// callback writes `RegistryState.encrypted_data`. Replaying these with
+    /// `transparency::verify_chain` must reproduce `transition_head`.
+    #[event]
+    pub struct RegistryTransitionEvent {
+        pub version: u64,
+        pub prev_head: [u8; 32],
+        pub state_hash: [u8; 32],
+        pub computation_offset: u64,
+        pub slot: u64,
+        pub new_head: [u8; 32],
+    }
+
     #[event]
     pub struct RegistrySizeEvent {
         pub total_users: u64,
     }
 
+    #[event]
+    pub struct AnomalyCountEvent {
+        /// Intersections suppressed by the per-query match cap, all time,
+        /// encrypted to the authority's x25519 key
+        pub result_ciphertext: [u8; 32],
+        pub result_nonce: [u8; 16],
+    }
+
+    /// Emitted when a PSI computation's output fails verification.
+    /// The session stays Failed, with the reason, until retried or closed.
+    #[event]
+    pub struct PsiFailedEvent {
+        pub user: Pubkey,
+        /// Offset the session was opened with (its PDA seed)
+        pub computation_offset: u64,
+        /// Offset of the attempt that failed; differs from
+        /// `computation_offset` after a retry_intersect
+        pub active_computation_offset: u64,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct RegistrationFailedEvent {
+        pub registry: Pubkey,
+        /// Registrant, or the registration queue for a discarded batch
+        pub user: Pubkey,
+        /// 0 when the computation is no longer known (see reclaimed_offset)
+        pub computation_offset: u64,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct BootstrapFailedEvent {
+        pub registry: Pubkey,
+        pub authority: Pubkey,
+        pub computation_offset: u64,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct RegistrySizeFailedEvent {
+        pub registry: Pubkey,
+        /// Payer of the reveal
+        pub user: Pubkey,
+        pub computation_offset: u64,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct AnomalyCountFailedEvent {
+        pub registry: Pubkey,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct CanaryInsertedEvent {
+        pub registry: Pubkey,
+        /// Whether the canary's bucket had room, encrypted to the
+        /// authority's x25519 key; false means nothing changed
+        pub inserted_ciphertext: [u8; 32],
+        pub inserted_nonce: [u8; 16],
+    }
+
+    #[event]
+    pub struct CanaryInsertFailedEvent {
+        pub registry: Pubkey,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct CanaryHitsEvent {
+        /// Canary hit count, encrypted to the authority's x25519 key
+        pub result_ciphertext: [u8; 32],
+        pub result_nonce: [u8; 16],
+    }
+
+    #[event]
+    pub struct CanaryHitsFailedEvent {
+        pub registry: Pubkey,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct PsiCancelledEvent {
+        pub user: Pubkey,
+        pub computation_offset: u64,
+    }
+
+    /// Emitted when a stuck session is reaped by expire_session; clients
+    /// polling the session should stop and retry with a new computation.
+    #[event]
+    pub struct PsiExpiredEvent {
+        pub user: Pubkey,
+        /// Offset the session was opened with (its PDA seed)
+        pub computation_offset: u64,
+        /// Offset of the attempt that timed out; differs from
+        /// `computation_offset` after a retry_intersect
+        pub active_computation_offset: u64,
+        pub created_at: i64,
+        pub expired_at: i64,
+        /// True when the retry limit was reached and the session was closed
+        /// with its rent refunded to the user
+        pub closed: bool,
+    }
+
+    #[event]
+    pub struct RegistryPauseEvent {
+        pub paused: u8,
+    }
+
+    #[event]
+    pub struct RegistryResetArmedEvent {
+        pub registry: Pubkey,
+        pub authority: Pubkey,
+    }
+
+    #[event]
+    pub struct FeesUpdatedEvent {
+        /// None when fees are charged in lamports
+        pub fee_mint: Option<Pubkey>,
+        pub query_fee: u64,
+        pub registration_fee: u64,
+        pub registration_stake: u64,
+    }
+
+    #[event]
+    pub struct FeesWithdrawnEvent {
+        pub authority: Pubkey,
+        /// None for a lamport withdrawal
+        pub fee_mint: Option<Pubkey>,
+        pub amount: u64,
+    }
+
+    #[event]
+    pub struct StakeDepositedEvent {
+        pub user: Pubkey,
+        /// None for a lamport stake
+        pub stake_mint: Option<Pubkey>,
+        pub amount: u64,
+    }
+
+    #[event]
+    pub struct StakeWithdrawnEvent {
+        pub user: Pubkey,
+        pub stake_mint: Option<Pubkey>,
+        pub amount: u64,
+    }
+
+    #[event]
+    pub struct StakeSlashedEvent {
+        pub user: Pubkey,
+        pub authority: Pubkey,
+        pub stake_mint: Option<Pubkey>,
+        pub amount: u64,
+    }
+
+    #[event]
+    pub struct UserDeregisteredEvent {
+        pub registry: Pubkey,
+        pub user: Pubkey,
+    }
+
+    #[event]
+    pub struct DeregistrationFailedEvent {
+        pub user: Pubkey,
+        pub computation_offset: u64,
+        pub reason: u8,
+    }
+
+    /// A stranded registration was settled; `status` is the state it was
+    /// stranded in.
+    #[event]
+    pub struct RegistrationRecoveredEvent {
+        pub user: Pubkey,
+        pub computation_offset: u64,
+        pub status: RegistrationStatus,
+    }
+
+    #[event]
+    pub struct RegistrationUpdatedEvent {
+        pub registry: Pubkey,
+        pub user: Pubkey,
+    }
+
+    #[event]
+    pub struct RegistrationUpdateFailedEvent {
+        pub user: Pubkey,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct MembershipCheckedEvent {
+        pub user: Pubkey,
+        pub computation_offset: u64,
+    }
+
+    #[event]
+    pub struct MembershipCheckFailedEvent {
+        pub user: Pubkey,
+        pub computation_offset: u64,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct HitCountEvent {
+        pub user: Pubkey,
+        /// Match count, encrypted to the requester's x25519 key
+        pub result_ciphertext: [u8; 32],
+        pub result_nonce: [u8; 16],
+    }
+
+    #[event]
+    pub struct HitCountFailedEvent {
+        pub user: Pubkey,
+        pub reason: u8,
+    }
+
+    #[event]
+    pub struct MatchRateLimitUpdatedEvent {
+        /// 0 when the cap is disabled
+        pub matches_per_epoch: u64,
+        pub epoch_secs: i64,
+    }
+
+    #[event]
+    pub struct QueryMatchCapUpdatedEvent {
+        /// 0 when the cap is disabled
+        pub max_matches: u64,
+    }
+
+    #[event]
+    pub struct AttestationKeyUpdatedEvent {
+        /// None when registrations no longer require attestation
+        pub attestation_key: Option<Pubkey>,
+    }
+
+    #[event]
+    pub struct AuthorityTransferredEvent {
+        pub previous_authority: Pubkey,
+        pub new_authority: Pubkey,
+    }
+
     // ── Error Codes ─────────────────────────────────────────────────────────
 
     #[error_code]
     pub enum ErrorCode {
-        #[msg("MXE computation output verification failed")]
-        VerificationFailed,
         #[msg("Registry is at maximum capacity")]
         RegistryFull,
         #[msg("Session has already been completed")]
         SessionAlreadyComplete,
-        #[msg("Unauthorized: only the session owner can retrieve results")]
+        #[msg("Unauthorized: only the session owner can manage this session")]
         Unauthorized,
         #[msg("Arcium cluster not configured on MXE account")]
         ClusterNotSet,
+        #[msg("Registry is paused for this operation")]
+        RegistryPaused,
+        #[msg("Unauthorized: signer is not the registry authority")]
+        NotRegistryAuthority,
+        #[msg("Unauthorized: signer is not the pending registry authority")]
+        NotPendingAuthority,
+        #[msg("Unknown pause flags")]
+        InvalidPauseFlags,
+        #[msg("Registry has not been bootstrapped with MXE state")]
+        RegistryNotBootstrapped,
+        #[msg("Registry is already bootstrapped; arm a reset first")]
+        RegistryAlreadyBootstrapped,
+        #[msg("Session is still computing; cancel it instead")]
+        SessionStillComputing,
+        #[msg("Session has not reached its timeout yet")]
+        SessionNotExpired,
+        #[msg("Session timeout must be positive")]
+        InvalidSessionTimeout,
+        #[msg("Session can only be retried after it failed or timed out")]
+        SessionNotRetryable,
+        #[msg("Session has reached the maximum number of retries")]
+        RetryLimitReached,
+        #[msg("Callback account does not belong to the computation being finalized")]
+        CallbackAccountMismatch,
+        #[msg("Illegal session state transition")]
+        InvalidSessionTransition,
+        #[msg("Session is not computing")]
+        SessionNotComputing,
+        #[msg("Another registry write is in flight; retry after its callback")]
+        RegistryWriteLocked,
+        #[msg("Registration queue is full")]
+        RegistrationQueueFull,
+        #[msg("Registration queue is empty")]
+        RegistrationQueueEmpty,
+        #[msg("Registry shard accounts are missing, out of order, or invalid")]
+        InvalidRegistryShards,
+        #[msg("Token account mint does not match the configured fee mint")]
+        FeeMintMismatch,
+        #[msg("Fees are charged in an SPL token; pass the token accounts and token program")]
+        FeeTokenAccountsMissing,
+        #[msg("Treasury balance is too low for this withdrawal")]
+        InsufficientTreasuryBalance,
+        #[msg("Registration account is missing, out of order, or invalid")]
+        InvalidRegistration,
+        #[msg("Stake unit does not match the configured fee mint")]
+        StakeMintMismatch,
+        #[msg("Stake is held in an SPL token; pass the token accounts and token program")]
+        StakeTokenAccountsMissing,
+        #[msg("Registration escrow does not hold the required stake")]
+        InsufficientStake,
+        #[msg("Registration is not in the Registered state")]
+        NotRegistered,
+        #[msg("Stake is locked while the wallet has a registration; deregister first")]
+        StakeLocked,
+        #[msg("Stake has already been slashed")]
+        NothingToSlash,
+        #[msg("Registration requires an attestation signature instruction before it")]
+        AttestationMissing,
+        #[msg("Attestation signature does not match the attestation key or registration")]
+        InvalidAttestation,
+        #[msg("Attestation has expired")]
+        AttestationExpired,
+        #[msg("Match epoch must be positive")]
+        InvalidMatchEpoch,
+        #[msg("Registry still has live shards or queued registrations")]
+        RegistryNotEmpty,
+        #[msg("Registration is not waiting on a timed-out computation")]
+        RegistrationNotStranded,
+        #[msg("Session's computation holds the registry write lock; expire it instead")]
+        SessionHoldsWriteLock,
+        #[msg("Query fee must be positive")]
+        InvalidQueryFee,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn registry_with(encrypted_data: Vec<u8>) -> RegistryState {
+        RegistryState {
+            bump: 254,
+            encrypted_data,
+            nonce: u128::MAX,
+            authority: Pubkey::new_unique(),
+            computation_count: 7,
+            pending_authority: Some(Pubkey::new_unique()),
+            paused: PAUSE_ALL,
+            bootstrapped: true,
+            reset_armed: false,
+            session_timeout_secs: DEFAULT_SESSION_TIMEOUT_SECS,
+            failed_computations: 1,
+            last_failure_reason: FAILURE_STALE_WRITE,
+            write_lock_offset: Some(42),
+            write_locked_at: 1_700_000_000,
+            version: 3,
+            state_hash: [0xaa; 32],
+            transition_head: [0xbb; 32],
+            attestation_key: Some(Pubkey::new_unique()),
+            match_cap_per_epoch: 5,
+            match_epoch_secs: DEFAULT_MATCH_EPOCH_SECS,
+            max_matches_per_query: 16,
+            reclaimed_lock_offset: Some(41),
+            reveal_offset: None,
+        }
+    }
+
+    #[test]
+    fn encrypted_data_region_matches_serialized_registry() {
+        let ciphertexts: Vec<u8> = (0..REGISTRY_HEADER_CIPHERTEXTS * CIPHERTEXT_SIZE)
+            .map(|i| i as u8)
+            .collect();
+        let registry = registry_with(ciphertexts.clone());
+
+        let mut account = Vec::new();
+        registry.try_serialize(&mut account).unwrap();
+
+        let (offset, len) = encrypted_data_region(&registry.encrypted_data);
+        let region = &account[offset as usize..(offset + len) as usize];
+        assert_eq!(region, &ciphertexts[..]);
+        assert_eq!(account.len(), RegistryState::SPACE);
+    }
+
+    #[test]
+    fn encrypted_data_region_matches_serialized_shard() {
+        let ciphertexts: Vec<u8> = (0..REGISTRY_SHARD_CIPHERTEXTS * CIPHERTEXT_SIZE)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let shard = RegistryShard {
+            bump: 253,
+            encrypted_data: ciphertexts.clone(),
+            nonce: 9,
+            index: 3,
+        };
+
+        let mut account = Vec::new();
+        shard.try_serialize(&mut account).unwrap();
+
+        let (offset, len) = encrypted_data_region(&shard.encrypted_data);
+        let region = &account[offset as usize..(offset + len) as usize];
+        assert_eq!(region, &ciphertexts[..]);
+        assert_eq!(account.len(), RegistryShard::SPACE);
+    }
+
+    #[test]
+    fn encrypted_data_region_matches_serialized_registration() {
+        let ciphertexts: Vec<u8> = (0..RECEIPT_CIPHERTEXTS * CIPHERTEXT_SIZE)
+            .map(|i| i as u8)
+            .collect();
+        let registration = Registration {
+            bump: 252,
+            encrypted_receipt: ciphertexts.clone(),
+            receipt_nonce: 5,
+            user: Pubkey::new_unique(),
+            status: RegistrationStatus::Registered,
+            computation_offset: 42,
+            created_at: 1_700_000_000,
+            updated_at: 1_700_000_060,
+        };
+
+        let mut account = Vec::new();
+        registration.try_serialize(&mut account).unwrap();
+
+        let (offset, len) = encrypted_data_region(&registration.encrypted_receipt);
+        let region = &account[offset as usize..(offset + len) as usize];
+        assert_eq!(region, &ciphertexts[..]);
+        assert_eq!(account.len(), Registration::SPACE);
+    }
+
+    /// Value of `pub const <name>: usize = <literal>;` in the circuit source.
+    fn circuit_const(source: &str, name: &str) -> usize {
+        let prefix = format!("pub const {}: usize = ", name);
+        source
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(prefix.as_str()))
+            .and_then(|value| value.trim_end_matches(';').parse().ok())
+            .unwrap_or_else(|| panic!("circuit constant {} not found", name))
+    }
+
+    #[test]
+    fn layout_constants_match_the_circuit() {
+        let circuit = include_str!("../../../encrypted-ixs/src/lib.rs");
+        assert_eq!(NUM_REGISTRY_SHARDS, circuit_const(circuit, "NUM_SHARDS"));
+        assert_eq!(
+            BUCKETS_PER_SHARD,
+            circuit_const(circuit, "BUCKETS_PER_SHARD")
+        );
+        assert_eq!(BUCKET_SIZE, circuit_const(circuit, "BUCKET_SIZE"));
+        assert_eq!(
+            MATCH_RESULT_CIPHERTEXTS,
+            circuit_const(circuit, "MAX_CLIENT_CONTACTS") + 1
+        );
+        assert_eq!(
+            REGISTRATION_BATCH_SIZE,
+            circuit_const(circuit, "REGISTRATION_BATCH_SIZE")
+        );
+    }
+
+    #[test]
+    fn registry_writes_fit_their_callback_transactions() {
+        let shard_bytes = RegistryShard::SPACE - 8 - 1 - 4 - 1;
+        assert!(REGISTRY_WRITE_OUTPUT_BYTES > NUM_REGISTRY_SHARDS * shard_bytes);
+        let capacity = REGISTRY_WRITE_CALLBACK_TXS as usize * CALLBACK_TX_OUTPUT_BYTES;
+        assert!(capacity >= REGISTRY_WRITE_OUTPUT_BYTES);
+        assert!(capacity - REGISTRY_WRITE_OUTPUT_BYTES < CALLBACK_TX_OUTPUT_BYTES);
+    }
+
+    #[test]
+    fn match_limit_rolls_over_per_epoch() {
+        let mut registry = registry_with(vec![]);
+        let day = DEFAULT_MATCH_EPOCH_SECS;
+
+        assert_eq!(registry.match_limit(3 * day), (3, 5));
+        assert_eq!(registry.match_limit(4 * day - 1), (3, 5));
+        assert_eq!(registry.match_limit(4 * day), (4, 5));
+
+        registry.match_cap_per_epoch = 0;
+        assert_eq!(registry.match_limit(4 * day), (4, u64::MAX));
+    }
+
+    #[test]
+    fn zero_query_match_cap_means_uncapped() {
+        let mut registry = registry_with(vec![]);
+        assert_eq!(registry.query_match_cap(), 16);
+
+        registry.max_matches_per_query = 0;
+        assert_eq!(registry.query_match_cap(), u64::MAX);
+    }
+
+    #[test]
+    fn abandoned_write_lock_is_marked_reclaimed() {
+        let mut registry = registry_with(vec![]);
+
+        // Another computation's lock is left alone
+        registry.abandon_write_lock(7);
+        assert_eq!(registry.write_lock_offset, Some(42));
+        assert_eq!(registry.reclaimed_lock_offset, Some(41));
+
+        registry.abandon_write_lock(42);
+        assert_eq!(registry.write_lock_offset, None);
+        assert_eq!(registry.reclaimed_lock_offset, Some(42));
+    }
+
+    #[test]
+    fn only_timed_out_registrations_off_the_lock_are_stranded() {
+        let timeout = DEFAULT_SESSION_TIMEOUT_SECS;
+        let mut registration = Registration {
+            bump: 252,
+            encrypted_receipt: vec![],
+            receipt_nonce: 0,
+            user: Pubkey::new_unique(),
+            status: RegistrationStatus::Pending,
+            computation_offset: 42,
+            created_at: 1_700_000_000,
+            updated_at: 1_700_000_000,
+        };
+        let deadline = registration.updated_at + timeout;
+
+        // Still holding the lock, or not yet timed out
+        assert!(!registration.is_stranded(Some(42), deadline, timeout));
+        assert!(!registration.is_stranded(None, deadline - 1, timeout));
+        // Lock released or taken over by another computation
+        assert!(registration.is_stranded(None, deadline, timeout));
+        assert!(registration.is_stranded(Some(43), deadline, timeout));
+
+        registration.status = RegistrationStatus::Deregistering;
+        assert!(registration.is_stranded(None, deadline, timeout));
+        registration.status = RegistrationStatus::Updating;
+        assert!(registration.is_stranded(None, deadline, timeout));
+        assert!(!registration.is_stranded(Some(42), deadline, timeout));
+
+        // Settled registrations and queued entries are never stranded
+        registration.status = RegistrationStatus::Registered;
+        assert!(!registration.is_stranded(None, deadline, timeout));
+        registration.status = RegistrationStatus::Pending;
+        registration.computation_offset = 0;
+        assert!(!registration.is_stranded(None, deadline, timeout));
+    }
+
+    fn session_with(status: SessionStatus) -> PsiSession {
+        PsiSession {
+            bump: 255,
+            user: Pubkey::new_unique(),
+            computation_offset: 42,
+            result_ciphertext: vec![],
+            result_nonce: [0; 16],
+            status,
+            created_at: 1_700_000_000,
+            attempt_started_at: 1_700_000_000,
+            failure_reason: FAILURE_NONE,
+            active_computation_offset: 42,
+            retry_count: 0,
+            retry_history: vec![],
+            registry_version: 3,
+        }
+    }
+
+    #[test]
+    fn expired_session_is_retryable_until_the_retry_limit() {
+        let timeout = DEFAULT_SESSION_TIMEOUT_SECS;
+        let mut session = session_with(SessionStatus::Computing);
+        let deadline = session.created_at + timeout;
+
+        assert!(session.expire(deadline - 1, timeout).is_err());
+        assert_eq!(session.status, SessionStatus::Computing);
+
+        session.expire(deadline, timeout).unwrap();
+        assert_eq!(session.status, SessionStatus::Expired);
+        assert_eq!(session.failure_reason, FAILURE_TIMEOUT);
+        assert!(session.expire(deadline, timeout).is_err());
+
+        // An expired session with retries left stays open for retry_intersect
+        assert!(!session.retries_exhausted());
+        session.transition(SessionStatus::Computing).unwrap();
+
+        // Past the retry limit expire_session closes it instead
+        session.retry_count = MAX_PSI_RETRIES as u8;
+        session.expire(deadline, timeout).unwrap();
+        assert!(session.retries_exhausted());
+    }
+
+    #[test]
+    fn terminal_states_have_no_transitions() {
+        use SessionStatus::*;
+        let all = [Pending, Computing, Completed, Failed, Cancelled, Expired];
+        for from in all {
+            let has_next = all.iter().any(|&to| from.can_transition_to(to));
+            assert_eq!(from.is_terminal(), !has_next, "{:?}", from);
+        }
+    }
+
+    #[test]
+    fn late_callback_outcome_is_dropped_without_failing() {
+        let mut session = session_with(SessionStatus::Computing);
+        session.expire(session.created_at + 1, 1).unwrap();
+
+        assert!(!session.settle(SessionStatus::Completed, FAILURE_NONE));
+        assert!(!session.settle(SessionStatus::Failed, FAILURE_VERIFICATION));
+        assert_eq!(session.status, SessionStatus::Expired);
+        assert_eq!(session.failure_reason, FAILURE_TIMEOUT);
+
+        let mut session = session_with(SessionStatus::Computing);
+        assert!(session.settle(SessionStatus::Failed, FAILURE_VERIFICATION));
+        assert_eq!(session.status, SessionStatus::Failed);
+        assert_eq!(session.failure_reason, FAILURE_VERIFICATION);
+    }
+
+    #[test]
+    fn membership_session_settles_once() {
+        let mut session = MembershipSession {
+            bump: 255,
+            user: Pubkey::new_unique(),
+            computation_offset: 7,
+            result_ciphertext: [0u8; 32],
+            result_nonce: [0u8; 16],
+            status: SessionStatus::Pending,
+            created_at: 1_700_000_000,
+            failure_reason: FAILURE_NONE,
+        };
+        assert!(!session.settle(SessionStatus::Completed, FAILURE_NONE));
+
+        session.transition(SessionStatus::Computing).unwrap();
+        assert!(session.transition(SessionStatus::Computing).is_err());
+        assert!(session.settle(SessionStatus::Completed, FAILURE_NONE));
+
+        // A late failure cannot overwrite the stored result
+        assert!(!session.settle(SessionStatus::Failed, FAILURE_VERIFICATION));
+        assert_eq!(session.status, SessionStatus::Completed);
+        assert_eq!(session.failure_reason, FAILURE_NONE);
+    }
+
+    #[test]
+    fn retry_records_the_superseded_attempt() {
+        let timeout = DEFAULT_SESSION_TIMEOUT_SECS;
+        let mut session = session_with(SessionStatus::Computing);
+        let start = session.created_at;
+
+        // Still computing within the timeout: nothing to retry
+        assert!(session.begin_retry(43, start + 1, timeout, 4).is_err());
+        assert_eq!(session.retry_count, 0);
+
+        // Past the timeout the attempt is expired and superseded
+        let retried_at = start + timeout;
+        session.begin_retry(43, retried_at, timeout, 4).unwrap();
+        assert_eq!(session.status, SessionStatus::Computing);
+        assert_eq!(session.active_computation_offset, 43);
+        assert_eq!(session.retry_count, 1);
+        assert_eq!(session.registry_version, 4);
+        assert_eq!(session.failure_reason, FAILURE_NONE);
+        assert_eq!(session.retry_history.len(), 1);
+        assert_eq!(session.retry_history[0].computation_offset, 42);
+        assert_eq!(session.retry_history[0].failure_reason, FAILURE_TIMEOUT);
+        assert_eq!(session.retry_history[0].retried_at, retried_at);
+
+        // The timeout restarts with the new attempt; created_at is kept
+        assert_eq!(session.created_at, start);
+        assert_eq!(session.attempt_started_at, retried_at);
+        assert!(session.expire(retried_at + timeout - 1, timeout).is_err());
+
+        // Expired by the permissionless crank first: the retry still applies
+        session.expire(retried_at + timeout, timeout).unwrap();
+        session
+            .begin_retry(44, retried_at + timeout, timeout, 5)
+            .unwrap();
+        assert_eq!(session.retry_count, 2);
+    }
+
+    #[test]
+    fn retry_stops_at_the_retry_limit() {
+        let mut session = session_with(SessionStatus::Failed);
+        for offset in 0..MAX_PSI_RETRIES as u64 {
+            session.begin_retry(100 + offset, 0, 1, 0).unwrap();
+            session.transition(SessionStatus::Failed).unwrap();
+        }
+        assert!(session.begin_retry(200, 0, 1, 0).is_err());
+        assert_eq!(session.retry_count as usize, MAX_PSI_RETRIES);
+        assert_eq!(session.retry_history.len(), MAX_PSI_RETRIES);
+    }
+
+    /// Ed25519 program instruction in the layout web3.js produces: header,
+    /// one offsets record, then public key, signature and message.
+    fn ed25519_ix(signer: &Pubkey, message: &[u8]) -> Instruction {
+        let (key_offset, signature_offset) = (16u16, 48u16);
+        let message_offset = signature_offset + 64;
+        let mut data = vec![1u8, 0];
+        for field in [
+            signature_offset,
+            u16::MAX,
+            key_offset,
+            u16::MAX,
+            message_offset,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(&[7u8; 64]);
+        data.extend_from_slice(message);
+        Instruction {
+            program_id: ed25519_program::ID,
+            accounts: vec![],
+            data,
+        }
+    }
+
+    #[test]
+    fn ed25519_instruction_verifies_only_the_bound_registration() {
+        let attester = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let attested = AttestedHash {
+            encrypted_hash: [1; 32],
+            pub_key: [2; 32],
+            nonce: 3,
+        };
+        let commitment = attested.commitment();
+        let message = attestation_message(&wallet, &commitment, 1_700_000_000);
+        let ix = ed25519_ix(&attester, &message);
+        assert!(ed25519_instruction_verifies(&ix, &attester, &message));
+
+        // Another key, wallet, ciphertext or expiry is rejected
+        assert!(!ed25519_instruction_verifies(
+            &ix,
+            &Pubkey::new_unique(),
+            &message
+        ));
+        let other_wallet = attestation_message(&Pubkey::new_unique(), &commitment, 1_700_000_000);
+        assert!(!ed25519_instruction_verifies(&ix, &attester, &other_wallet));
+        let other_hash = attestation_message(
+            &wallet,
+            &registration_commitment(&[9; 32], &[2; 32], 3),
+            1_700_000_000,
+        );
+        assert!(!ed25519_instruction_verifies(&ix, &attester, &other_hash));
+        let later = attestation_message(&wallet, &commitment, 1_800_000_000);
+        assert!(!ed25519_instruction_verifies(&ix, &attester, &later));
+
+        // Offsets into another instruction are rejected
+        let mut foreign = ix.clone();
+        foreign.data[14..16].copy_from_slice(&0u16.to_le_bytes());
+        assert!(!ed25519_instruction_verifies(&foreign, &attester, &message));
+
+        // Only the Ed25519 program's instruction counts
+        let mut spoofed = ix;
+        spoofed.program_id = Pubkey::new_unique();
+        assert!(!ed25519_instruction_verifies(&spoofed, &attester, &message));
     }
 }
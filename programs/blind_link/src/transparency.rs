@@ -0,0 +1,306 @@
+// ============================================================================
+// Project Blind-Link: Registry Transparency Log Verifier (off-chain)
+// ============================================================================
+// Every callback that rewrites the registry header and shards extends a hash
+// chain and emits a `RegistryTransitionEvent`. Replaying those events from
+// genesis and comparing the result with the on-chain `transition_head` proves
+// the registry was never rolled back or swapped outside the program.
+//
+// Usage: collect the "Program data:" payloads of the program's transactions,
+// base64-decode them, keep what `decode_event` accepts, then call
+// `verify_registry` with the fetched `RegistryState`. `verify_snapshot`
+// additionally checks the fetched shards against the committed state hash.
+// ============================================================================
+
+use std::fmt;
+
+use anchor_lang::{AnchorDeserialize, Discriminator};
+
+use crate::blind_link::RegistryTransitionEvent;
+use crate::{
+    registry_snapshot_hash, registry_state_hash, transition_link, RegistryShard, RegistryState,
+};
+
+/// Head of the chain before the first transition.
+pub const GENESIS_HEAD: [u8; 32] = [0u8; 32];
+
+/// Reason a replayed transparency log was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    /// Event versions are not consecutive (missing or reordered events)
+    VersionGap { expected: u64, found: u64 },
+    /// The event's `prev_head` is not the head replayed so far
+    BrokenLink { version: u64 },
+    /// The event's `new_head` is not the hash of its own fields
+    BadLink { version: u64 },
+    /// The replayed head differs from `RegistryState.transition_head`
+    HeadMismatch,
+    /// The last event's state hash differs from `RegistryState.state_hash`
+    StateMismatch,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::VersionGap { expected, found } => {
+                write!(f, "expected version {}, found {}", expected, found)
+            }
+            ChainError::BrokenLink { version } => {
+                write!(f, "version {} does not extend the previous head", version)
+            }
+            ChainError::BadLink { version } => {
+                write!(f, "version {} has an inconsistent new_head", version)
+            }
+            ChainError::HeadMismatch => write!(f, "replayed head differs from on-chain head"),
+            ChainError::StateMismatch => {
+                write!(f, "last transition differs from on-chain state hash")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// Decode a `RegistryTransitionEvent` from a base64-decoded "Program data:"
+/// log payload. Returns `None` for any other event.
+pub fn decode_event(data: &[u8]) -> Option<RegistryTransitionEvent> {
+    let body = data.strip_prefix(RegistryTransitionEvent::DISCRIMINATOR)?;
+    RegistryTransitionEvent::try_from_slice(body).ok()
+}
+
+/// Replay `events` starting after `head` at `version`, returning the new
+/// head. Events must be in version order with no gaps.
+pub fn replay_from(
+    mut head: [u8; 32],
+    mut version: u64,
+    events: &[RegistryTransitionEvent],
+) -> Result<[u8; 32], ChainError> {
+    for event in events {
+        let expected = version + 1;
+        if event.version != expected {
+            return Err(ChainError::VersionGap {
+                expected,
+                found: event.version,
+            });
+        }
+        if event.prev_head != head {
+            return Err(ChainError::BrokenLink {
+                version: event.version,
+            });
+        }
+
+        let link = transition_link(
+            &event.prev_head,
+            &event.state_hash,
+            event.computation_offset,
+            event.slot,
+        );
+        if link != event.new_head {
+            return Err(ChainError::BadLink {
+                version: event.version,
+            });
+        }
+
+        head = link;
+        version = event.version;
+    }
+    Ok(head)
+}
+
+/// Replay the full log from genesis, returning the final head.
+pub fn verify_chain(events: &[RegistryTransitionEvent]) -> Result<[u8; 32], ChainError> {
+    replay_from(GENESIS_HEAD, 0, events)
+}
+
+/// Replay the full log from genesis and check it ends at the registry's
+/// current head and state commitment.
+pub fn verify_registry(
+    registry: &RegistryState,
+    events: &[RegistryTransitionEvent],
+) -> Result<(), ChainError> {
+    let head = verify_chain(events)?;
+    if head != registry.transition_head {
+        return Err(ChainError::HeadMismatch);
+    }
+    if let Some(last) = events.last() {
+        if last.state_hash != registry.state_hash {
+            return Err(ChainError::StateMismatch);
+        }
+    }
+    Ok(())
+}
+
+/// Check that the fetched header and shards (in index order) are the
+/// snapshot `RegistryState.state_hash` commits to. Only meaningful once the
+/// registry has been bootstrapped.
+pub fn verify_snapshot(
+    registry: &RegistryState,
+    shards: &[RegistryShard],
+) -> Result<(), ChainError> {
+    let shard_hashes: Vec<[u8; 32]> = shards
+        .iter()
+        .map(|shard| registry_state_hash(&shard.encrypted_data, shard.nonce))
+        .collect();
+    let header_hash = registry_state_hash(&registry.encrypted_data, registry.nonce);
+    if registry_snapshot_hash(&header_hash, &shard_hashes) != registry.state_hash {
+        return Err(ChainError::StateMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::AnchorSerialize;
+
+    use super::*;
+    use crate::tests::registry_with;
+
+    /// A consistent log of `len` transitions from genesis.
+    fn chain(len: u64) -> Vec<RegistryTransitionEvent> {
+        let mut head = GENESIS_HEAD;
+        (1..=len)
+            .map(|version| {
+                let state_hash = [version as u8; 32];
+                let new_head = transition_link(&head, &state_hash, 100 + version, 1_000 + version);
+                let event = RegistryTransitionEvent {
+                    version,
+                    prev_head: head,
+                    state_hash,
+                    computation_offset: 100 + version,
+                    slot: 1_000 + version,
+                    new_head,
+                };
+                head = new_head;
+                event
+            })
+            .collect()
+    }
+
+    /// A registry whose head and state hash match the end of `events`.
+    fn registry_at(events: &[RegistryTransitionEvent]) -> RegistryState {
+        let mut registry = registry_with(vec![]);
+        let last = events.last().unwrap();
+        registry.version = last.version;
+        registry.transition_head = last.new_head;
+        registry.state_hash = last.state_hash;
+        registry
+    }
+
+    #[test]
+    fn decode_event_accepts_only_transition_events() {
+        let event = chain(1).remove(0);
+        let mut data = RegistryTransitionEvent::DISCRIMINATOR.to_vec();
+        event.serialize(&mut data).unwrap();
+
+        let decoded = decode_event(&data).unwrap();
+        assert_eq!(decoded.version, event.version);
+        assert_eq!(decoded.new_head, event.new_head);
+
+        data[0] ^= 1;
+        assert!(decode_event(&data).is_none());
+        assert!(decode_event(RegistryTransitionEvent::DISCRIMINATOR).is_none());
+    }
+
+    #[test]
+    fn valid_chain_replays_to_the_registry_head() {
+        let events = chain(3);
+        assert_eq!(verify_chain(&events), Ok(events[2].new_head));
+        assert_eq!(verify_registry(&registry_at(&events), &events), Ok(()));
+
+        // Replaying a suffix from a known head reaches the same head
+        assert_eq!(
+            replay_from(events[0].new_head, 1, &events[1..]),
+            Ok(events[2].new_head)
+        );
+    }
+
+    #[test]
+    fn missing_event_is_a_version_gap() {
+        let mut events = chain(3);
+        events.remove(1);
+        assert_eq!(
+            verify_chain(&events),
+            Err(ChainError::VersionGap {
+                expected: 2,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn event_off_the_replayed_head_is_a_broken_link() {
+        let mut events = chain(3);
+        events[1].prev_head = [0xee; 32];
+        assert_eq!(
+            verify_chain(&events),
+            Err(ChainError::BrokenLink { version: 2 })
+        );
+    }
+
+    #[test]
+    fn tampered_event_fails_its_link() {
+        let mut events = chain(3);
+        events[2].state_hash = [0xee; 32];
+        assert_eq!(
+            verify_chain(&events),
+            Err(ChainError::BadLink { version: 3 })
+        );
+    }
+
+    #[test]
+    fn registry_with_another_head_or_state_is_rejected() {
+        let events = chain(3);
+
+        let mut registry = registry_at(&events);
+        registry.transition_head = [0xee; 32];
+        assert_eq!(
+            verify_registry(&registry, &events),
+            Err(ChainError::HeadMismatch)
+        );
+        // A truncated log stops short of the on-chain head
+        assert_eq!(
+            verify_registry(&registry_at(&events), &events[..2]),
+            Err(ChainError::HeadMismatch)
+        );
+
+        let mut registry = registry_at(&events);
+        registry.state_hash = [0xee; 32];
+        assert_eq!(
+            verify_registry(&registry, &events),
+            Err(ChainError::StateMismatch)
+        );
+    }
+
+    #[test]
+    fn snapshot_must_match_the_committed_state() {
+        let shards: Vec<RegistryShard> = (0..crate::NUM_REGISTRY_SHARDS as u8)
+            .map(|index| RegistryShard {
+                bump: 255,
+                encrypted_data: vec![index; 64],
+                nonce: index as u128,
+                index,
+            })
+            .collect();
+        let mut registry = registry_with(vec![0x11; 64]);
+        let shard_hashes: Vec<[u8; 32]> = shards
+            .iter()
+            .map(|shard| registry_state_hash(&shard.encrypted_data, shard.nonce))
+            .collect();
+        registry.state_hash = registry_snapshot_hash(
+            &registry_state_hash(&registry.encrypted_data, registry.nonce),
+            &shard_hashes,
+        );
+        assert_eq!(verify_snapshot(&registry, &shards), Ok(()));
+
+        let mut tampered = shards.clone();
+        tampered[1].encrypted_data[0] ^= 1;
+        assert_eq!(
+            verify_snapshot(&registry, &tampered),
+            Err(ChainError::StateMismatch)
+        );
+        assert_eq!(
+            verify_snapshot(&registry, &shards[..shards.len() - 1]),
+            Err(ChainError::StateMismatch)
+        );
+    }
+}
@@ -8,8 +8,8 @@
 
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
-import { x25519 } from "@noble/curves/ed25519";
-import { randomBytes } from "crypto";
+import { ed25519, x25519 } from "@noble/curves/ed25519";
+import { createHash, randomBytes } from "crypto";
 import { expect } from "chai";
 import {
   getArciumEnv,
@@ -70,15 +70,25 @@ describe("blind-link", () => {
   const arciumEnv = getArciumEnv();
 
   const REGISTRY_SEED = Buffer.from("blind_link_registry");
+  const REGISTRY_SHARD_SEED = Buffer.from("registry_shard");
+  const NUM_REGISTRY_SHARDS = 4;
   const SIGN_PDA_SEED = Buffer.from("ArciumSignerAccount");
+  const ESCROW_SEED = Buffer.from("registration_escrow");
+  const REGISTRATION_SEED = Buffer.from("registration");
+  const REGISTRATION_QUEUE_SEED = Buffer.from("registration_queue");
 
   let registryPda: anchor.web3.PublicKey;
   let registryBump: number;
+  let shardPdas: anchor.web3.PublicKey[];
   let signPda: anchor.web3.PublicKey;
   let mxeAccount: anchor.web3.PublicKey;
   let arciumProgramId: anchor.web3.PublicKey;
   let feePool: anchor.web3.PublicKey;
   let clockAccount: anchor.web3.PublicKey;
+  // Registrant enqueued by the queue test, deregistered after it
+  let carol: anchor.web3.Keypair;
+  // Ownership secret alice registers with, for the hit count test
+  const aliceOwnerSecret = deserializeLE(randomBytes(16));
 
   // ── Setup ───────────────────────────────────────────────────────────
 
@@ -92,6 +102,12 @@ describe("blind-link", () => {
       [SIGN_PDA_SEED],
       program.programId
     );
+    shardPdas = Array.from({ length: NUM_REGISTRY_SHARDS }, (_, i) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [REGISTRY_SHARD_SEED, Buffer.from([i])],
+        program.programId
+      )[0]
+    );
     mxeAccount = getMXEAccAddress(program.programId);
     arciumProgramId = getArciumProgramId();
     feePool = getFeePoolAccAddress();
@@ -129,6 +145,370 @@ describe("blind-link", () => {
     console.log("  Registry initialized:", tx);
   });
 
+  // Registry shards, in index order, as remaining accounts
+  const shardAccounts = () =>
+    shardPdas.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }));
+
+  const escrowPda = (user: anchor.web3.PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [ESCROW_SEED, user.toBuffer()],
+      program.programId
+    )[0];
+
+  const registrationPda = (user: anchor.web3.PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [REGISTRATION_SEED, user.toBuffer()],
+      program.programId
+    )[0];
+
+  const registrationQueuePda = () =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [REGISTRATION_QUEUE_SEED],
+      program.programId
+    )[0];
+
+  // Fresh registrant wallet, funded from the provider for stake, fees and rent
+  async function fundedKeypair(lamports = 50_000_000) {
+    const keypair = anchor.web3.Keypair.generate();
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.transfer({
+          fromPubkey: provider.wallet.publicKey,
+          toPubkey: keypair.publicKey,
+          lamports,
+        })
+      ),
+      [],
+      { commitment: "confirmed" }
+    );
+    return keypair;
+  }
+
+  // Open a registration escrow with the configured lamport stake, for
+  // `user` or the provider wallet
+  async function depositStake(user?: anchor.web3.Keypair) {
+    const owner = user?.publicKey ?? provider.wallet.publicKey;
+    await withRetry(() => program.methods
+      .depositStake()
+      .accountsPartial({
+        user: owner,
+        registrationEscrow: escrowPda(owner),
+      })
+      .signers(user ? [user] : [])
+      .rpc({ commitment: "confirmed" }));
+  }
+
+  // Salted-hash stand-in used throughout: SHA-256 truncated to a u128
+  async function hashContact(contact: string): Promise<bigint> {
+    const hashArray = new Uint8Array(
+      await crypto.subtle.digest("SHA-256", new TextEncoder().encode(contact))
+    );
+    let contactHash = BigInt(0);
+    for (let i = 0; i < 16; i++) {
+      contactHash |= BigInt(hashArray[i]) << BigInt(i * 8);
+    }
+    return contactHash;
+  }
+
+  // Attestation by `attester` that `user` verified `contact`: the attested
+  // hash (encrypted to the MXE), its expiry, and the ed25519 instruction
+  // that must precede the registration
+  async function signAttestation(
+    attester: anchor.web3.Keypair,
+    user: anchor.web3.PublicKey,
+    contact: string
+  ) {
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+    const attesterPrivateKey = x25519.utils.randomSecretKey();
+    const attesterPublicKey = x25519.getPublicKey(attesterPrivateKey);
+    const attestedNonce = randomBytes(16);
+    const [attestedHash] = new RescueCipher(
+      x25519.getSharedSecret(attesterPrivateKey, mxePublicKey)
+    ).encrypt([await hashContact(contact)], attestedNonce);
+
+    const expiry = Math.floor(Date.now() / 1000) + 600;
+    const expiryLe = Buffer.alloc(8);
+    expiryLe.writeBigInt64LE(BigInt(expiry));
+    const commitment = createHash("sha256")
+      .update(Uint8Array.from(attestedHash))
+      .update(attesterPublicKey)
+      .update(attestedNonce)
+      .digest();
+    const message = Buffer.concat([
+      Buffer.from("blind-link:attest:v1"),
+      user.toBuffer(),
+      commitment,
+      expiryLe,
+    ]);
+    return {
+      attested: {
+        encryptedHash: Array.from(Uint8Array.from(attestedHash)),
+        pubKey: Array.from(attesterPublicKey),
+        nonce: new anchor.BN(deserializeLE(attestedNonce).toString()),
+      },
+      expiry: new anchor.BN(expiry),
+      ed25519Ix: anchor.web3.Ed25519Program.createInstructionWithPublicKey({
+        publicKey: attester.publicKey.toBytes(),
+        message,
+        signature: ed25519.sign(message, attester.secretKey.slice(0, 32)),
+      }),
+    };
+  }
+
+  // Fold the head of the registration queue into the registry and wait
+  // for the callback
+  async function crankRegistrationQueue() {
+    const queuePda = registrationQueuePda();
+    const queue = await (program.account as any).registrationQueue.fetch(queuePda);
+
+    // Each batch entry's registration and wallet follow the shards, in
+    // queue order
+    const batchAccounts = queue.entries
+      .slice(0, 4)
+      .flatMap((entry: any) => [
+        { pubkey: registrationPda(entry.user), isWritable: true, isSigner: false },
+        { pubkey: entry.user, isWritable: true, isSigner: false },
+      ]);
+
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await withRetry(() => program.methods
+      .processRegistrationQueue(computationOffset)
+      .accountsPartial({
+        payer: provider.wallet.publicKey,
+        registryState: registryPda,
+        registrationQueue: queuePda,
+        signPdaAccount: signPda,
+        mxeAccount,
+        mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(
+          arciumEnv.arciumClusterOffset
+        ),
+        computationAccount: getComputationAccAddress(
+          arciumEnv.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          program.programId,
+          Buffer.from(
+            getCompDefAccOffset("process_registration_queue")
+          ).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+        poolAccount: feePool,
+        clockAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: arciumProgramId,
+      })
+      .remainingAccounts([...shardAccounts(), ...batchAccounts])
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      provider,
+      computationOffset,
+      program.programId,
+      "confirmed"
+    );
+  }
+
+  // Run one PSI query for `contacts` from the provider wallet and return
+  // the decrypted MatchResult (flags, then match count)
+  async function runIntersection(contacts: string[]): Promise<bigint[]> {
+    const clientPrivateKey = x25519.utils.randomSecretKey();
+    const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+    const sharedSecret = x25519.getSharedSecret(clientPrivateKey, mxePublicKey);
+    const cipher = new RescueCipher(sharedSecret);
+    const nonce = randomBytes(16);
+
+    const MAX_CLIENT_CONTACTS = 16;
+    const paddedHashes = new Array(MAX_CLIENT_CONTACTS).fill(BigInt(0));
+    for (let c = 0; c < contacts.length; c++) {
+      const hashArray = new Uint8Array(
+        await crypto.subtle.digest("SHA-256", new TextEncoder().encode(contacts[c]))
+      );
+      let h = BigInt(0);
+      for (let i = 0; i < 16; i++) {
+        h |= BigInt(hashArray[i]) << BigInt(i * 8);
+      }
+      paddedHashes[c] = h;
+    }
+    const ciphertexts = cipher.encrypt(
+      [...paddedHashes, BigInt(contacts.length)],
+      nonce
+    );
+
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const [sessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("psi_session"),
+        provider.wallet.publicKey.toBuffer(),
+        computationOffset.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+
+    await withRetry(() => program.methods
+      .intersectContacts(
+        computationOffset,
+        ciphertexts
+          .slice(0, MAX_CLIENT_CONTACTS)
+          .map((ct: number[]) => Array.from(Uint8Array.from(ct))),
+        Array.from(Uint8Array.from(ciphertexts[MAX_CLIENT_CONTACTS])),
+        Array.from(clientPublicKey) as number[],
+        new anchor.BN(deserializeLE(nonce).toString())
+      )
+      .accountsPartial({
+        user: provider.wallet.publicKey,
+        psiSession: sessionPda,
+        registryState: registryPda,
+        signPdaAccount: signPda,
+        mxeAccount,
+        mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(
+          arciumEnv.arciumClusterOffset
+        ),
+        computationAccount: getComputationAccAddress(
+          arciumEnv.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          program.programId,
+          Buffer.from(getCompDefAccOffset("intersect_contacts")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+        poolAccount: feePool,
+        clockAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: arciumProgramId,
+      })
+      .remainingAccounts(shardAccounts())
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      provider,
+      computationOffset,
+      program.programId,
+      "confirmed"
+    );
+
+    const session = await (program.account as any).psiSession.fetch(sessionPda);
+    expect(session.status).to.deep.equal({ completed: {} });
+    return cipher.decrypt(session.resultCiphertext, session.resultNonce);
+  }
+
+  // ── Test: Initialize Registry Shards ──────────────────────────────
+
+  it("initializes the registry shards", async () => {
+    for (let i = 0; i < NUM_REGISTRY_SHARDS; i++) {
+      const existing = await provider.connection.getAccountInfo(shardPdas[i]);
+      if (existing) continue;
+
+      await program.methods
+        .initializeRegistryShard(i)
+        .accountsPartial({
+          registryShard: shardPdas[i],
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    for (let i = 0; i < NUM_REGISTRY_SHARDS; i++) {
+      const shard = await (program.account as any).registryShard.fetch(shardPdas[i]);
+      expect(shard.index).to.equal(i);
+    }
+    console.log("  Registry shards initialized");
+  });
+
+  // ── Test: Initialize Fee Config ───────────────────────────────────
+
+  it("initializes the fee config with a positive query fee", async () => {
+    const [feeConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("fee_config")],
+      program.programId
+    );
+    const existing = await provider.connection.getAccountInfo(feeConfigPda);
+    if (!existing) {
+      await program.methods
+        .initializeFeeConfig(new anchor.BN(5000), new anchor.BN(0))
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    // Each query holds the registry write lock, so it can never be free
+    let error: any;
+    try {
+      await program.methods
+        .setFees(new anchor.BN(0), new anchor.BN(0))
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+    } catch (e: any) {
+      error = e;
+    }
+    expect(String(error)).to.include("InvalidQueryFee");
+
+    const config = await (program.account as any).feeConfig.fetch(feeConfigPda);
+    expect(config.feeMint).to.equal(null);
+    expect(config.queryFee.toNumber()).to.be.greaterThan(0);
+    console.log(
+      "  Fees (lamports): query",
+      config.queryFee.toString(),
+      "registration",
+      config.registrationFee.toString()
+    );
+  });
+
+  // ── Test: Resize Registry Accounts ────────────────────────────────
+
+  it("resizes the registry accounts to the circuit layout", async () => {
+    const resize = () =>
+      program.methods
+        .resizeRegistry()
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    // Growth is capped per instruction; repeat until the size settles
+    let size = -1;
+    for (let i = 0; i < 8; i++) {
+      await resize();
+      const info = await provider.connection.getAccountInfo(registryPda);
+      if (info!.data.length === size) break;
+      size = info!.data.length;
+    }
+    console.log("  Registry account size:", size);
+
+    // Shards are created at the CPI size cap and grown from there
+    for (let i = 0; i < NUM_REGISTRY_SHARDS; i++) {
+      let shardSize = -1;
+      for (let step = 0; step < 32; step++) {
+        await withRetry(() => program.methods
+          .resizeRegistryShard(i)
+          .accountsPartial({
+            registryShard: shardPdas[i],
+            registryState: registryPda,
+            authority: provider.wallet.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .rpc({ commitment: "confirmed" }));
+        const info = await provider.connection.getAccountInfo(shardPdas[i]);
+        if (info!.data.length === shardSize) break;
+        shardSize = info!.data.length;
+      }
+      console.log(`  Shard ${i} account size:`, shardSize);
+    }
+  });
+
   // ── Test: Initialize Computation Definitions ──────────────────────
 
   it("initializes computation definitions", async () => {
@@ -155,6 +535,18 @@ describe("blind-link", () => {
       { name: "intersect_contacts", method: "initIntersectContactsCompDef" },
       { name: "register_user", method: "initRegisterUserCompDef" },
       { name: "reveal_registry_size", method: "initRevealRegistrySizeCompDef" },
+      { name: "init_registry", method: "initInitRegistryCompDef" },
+      {
+        name: "process_registration_queue",
+        method: "initProcessRegistrationQueueCompDef",
+      },
+      { name: "deregister_user", method: "initDeregisterUserCompDef" },
+      { name: "update_registration", method: "initUpdateRegistrationCompDef" },
+      { name: "check_membership", method: "initCheckMembershipCompDef" },
+      { name: "read_my_hit_count", method: "initReadMyHitCountCompDef" },
+      { name: "reveal_anomaly_count", method: "initRevealAnomalyCountCompDef" },
+      { name: "insert_canary", method: "initInsertCanaryCompDef" },
+      { name: "reveal_canary_hits", method: "initRevealCanaryHitsCompDef" },
     ];
 
     for (const circuit of circuits) {
@@ -196,9 +588,76 @@ describe("blind-link", () => {
     console.log("  All computation definitions initialized");
   });
 
+  // ── Test: Bootstrap Registry ──────────────────────────────────────
+
+  it("bootstraps the registry with MXE-encrypted empty state", async () => {
+    const existing = await (program.account as any).registryState.fetch(registryPda);
+    if (existing.bootstrapped) {
+      console.log("  Registry already bootstrapped (skipped)");
+      return;
+    }
+
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+
+    const tx = await withRetry(() => program.methods
+      .queueInitRegistry(computationOffset)
+      .accountsPartial({
+        payer: provider.wallet.publicKey,
+        registryState: registryPda,
+        signPdaAccount: signPda,
+        mxeAccount,
+        mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(
+          arciumEnv.arciumClusterOffset
+        ),
+        computationAccount: getComputationAccAddress(
+          arciumEnv.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          program.programId,
+          Buffer.from(getCompDefAccOffset("init_registry")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+        poolAccount: feePool,
+        clockAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: arciumProgramId,
+      })
+      .remainingAccounts(shardAccounts())
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      provider,
+      computationOffset,
+      program.programId,
+      "confirmed"
+    );
+
+    const registry = await (program.account as any).registryState.fetch(registryPda);
+    expect(registry.bootstrapped).to.equal(true);
+    expect(registry.encryptedData.length).to.be.greaterThan(0);
+
+    console.log("  Registry bootstrapped:", tx);
+  });
+
   // ── Test: Register a User ─────────────────────────────────────────
 
   it("registers a user in the global registry", async () => {
+    const registration = await (program.account as any).registration.fetchNullable(
+      registrationPda(provider.wallet.publicKey)
+    );
+    if (registration) {
+      console.log("  Wallet already registered (skipped)");
+      return;
+    }
+    const escrow = await (program.account as any).registrationEscrow.fetchNullable(
+      escrowPda(provider.wallet.publicKey)
+    );
+    if (!escrow) {
+      await depositStake();
+    }
+
     // Setup crypto session
     const clientPrivateKey = x25519.utils.randomSecretKey();
     const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
@@ -220,9 +679,10 @@ describe("blind-link", () => {
       contactHash |= BigInt(hashArray[i]) << BigInt(i * 8);
     }
 
-    // Encrypt
-    const ciphertexts = cipher.encrypt([contactHash], nonce);
+    // Encrypt the hash and ownership secret under one nonce
+    const ciphertexts = cipher.encrypt([contactHash, aliceOwnerSecret], nonce);
     const encryptedHash = Uint8Array.from(ciphertexts[0]);
+    const encryptedOwnerSecret = Uint8Array.from(ciphertexts[1]);
 
     const computationOffset = new anchor.BN(randomBytes(8), "hex");
 
@@ -230,12 +690,17 @@ describe("blind-link", () => {
       .registerUser(
         computationOffset,
         Array.from(encryptedHash) as number[],
+        Array.from(encryptedOwnerSecret) as number[],
         Array.from(clientPublicKey) as number[],
-        new anchor.BN(deserializeLE(nonce).toString())
+        new anchor.BN(deserializeLE(nonce).toString()),
+        null, // attested: no attestation key configured
+        new anchor.BN(0) // attestation_expiry: ignored as well
       )
       .accountsPartial({
         user: provider.wallet.publicKey,
         registryState: registryPda,
+        registrationEscrow: escrowPda(provider.wallet.publicKey),
+        registration: registrationPda(provider.wallet.publicKey),
         signPdaAccount: signPda,
         mxeAccount,
         mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
@@ -256,6 +721,7 @@ describe("blind-link", () => {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: arciumProgramId,
       })
+      .remainingAccounts(shardAccounts())
       .rpc({ commitment: "confirmed" }));
 
     // Wait for MXE computation
@@ -376,6 +842,7 @@ describe("blind-link", () => {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: arciumProgramId,
       })
+      .remainingAccounts(shardAccounts())
       .rpc({ commitment: "confirmed" }));
 
     console.log("  PSI computation submitted:", tx);
@@ -390,7 +857,7 @@ describe("blind-link", () => {
 
     // Fetch and decrypt result
     const session = await (program.account as any).psiSession.fetch(sessionPda);
-    expect(session.status).to.equal(2); // completed
+    expect(session.status).to.deep.equal({ completed: {} });
 
     const decrypted = cipher.decrypt(
       session.resultCiphertext,
@@ -536,6 +1003,7 @@ describe("blind-link", () => {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: arciumProgramId,
       })
+      .remainingAccounts(shardAccounts())
       .rpc({ commitment: "confirmed" }));
 
     await awaitComputationFinalization(
@@ -546,7 +1014,7 @@ describe("blind-link", () => {
     );
 
     const session = await (program.account as any).psiSession.fetch(sessionPda);
-    expect(session.status).to.equal(2); // completed
+    expect(session.status).to.deep.equal({ completed: {} });
 
     const decrypted = cipher.decrypt(
       session.resultCiphertext,
@@ -651,6 +1119,7 @@ describe("blind-link", () => {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: arciumProgramId,
       })
+      .remainingAccounts(shardAccounts())
       .rpc({ commitment: "confirmed" }));
 
     await awaitComputationFinalization(
@@ -661,7 +1130,7 @@ describe("blind-link", () => {
     );
 
     const session = await (program.account as any).psiSession.fetch(sessionPda);
-    expect(session.status).to.equal(2);
+    expect(session.status).to.deep.equal({ completed: {} });
 
     const decrypted = cipher.decrypt(
       session.resultCiphertext,
@@ -769,6 +1238,7 @@ describe("blind-link", () => {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: arciumProgramId,
       })
+      .remainingAccounts(shardAccounts())
       .rpc({ commitment: "confirmed" }));
 
     await awaitComputationFinalization(
@@ -779,7 +1249,7 @@ describe("blind-link", () => {
     );
 
     const session = await (program.account as any).psiSession.fetch(sessionPda);
-    expect(session.status).to.equal(2);
+    expect(session.status).to.deep.equal({ completed: {} });
 
     const decrypted = cipher.decrypt(
       session.resultCiphertext,
@@ -797,7 +1267,10 @@ describe("blind-link", () => {
   // ── Test: Register Second User and Multi-Match PSI ──────────────
 
   it("registers a second user and finds multiple matches", async () => {
-    // Register bob@unknown.com
+    // Register bob@unknown.com from his own staked wallet
+    const bob = await fundedKeypair();
+    await depositStake(bob);
+
     const clientPrivateKey = x25519.utils.randomSecretKey();
     const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
     const mxePublicKey = await fetchMXEKey(provider, program.programId);
@@ -817,20 +1290,29 @@ describe("blind-link", () => {
       contactHash |= BigInt(hashArray[i]) << BigInt(i * 8);
     }
 
-    const regCiphertexts = cipher.encrypt([contactHash], nonce);
+    const regCiphertexts = cipher.encrypt(
+      [contactHash, deserializeLE(randomBytes(16))],
+      nonce
+    );
     const encryptedHash = Uint8Array.from(regCiphertexts[0]);
+    const encryptedOwnerSecret = Uint8Array.from(regCiphertexts[1]);
     const regOffset = new anchor.BN(randomBytes(8), "hex");
 
     await withRetry(() => program.methods
       .registerUser(
         regOffset,
         Array.from(encryptedHash) as number[],
+        Array.from(encryptedOwnerSecret) as number[],
         Array.from(clientPublicKey) as number[],
-        new anchor.BN(deserializeLE(nonce).toString())
+        new anchor.BN(deserializeLE(nonce).toString()),
+        null, // attested: no attestation key configured
+        new anchor.BN(0) // attestation_expiry: ignored as well
       )
       .accountsPartial({
-        user: provider.wallet.publicKey,
+        user: bob.publicKey,
         registryState: registryPda,
+        registrationEscrow: escrowPda(bob.publicKey),
+        registration: registrationPda(bob.publicKey),
         signPdaAccount: signPda,
         mxeAccount,
         mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
@@ -851,6 +1333,8 @@ describe("blind-link", () => {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: arciumProgramId,
       })
+      .remainingAccounts(shardAccounts())
+      .signers([bob])
       .rpc({ commitment: "confirmed" }));
 
     await awaitComputationFinalization(
@@ -946,6 +1430,7 @@ describe("blind-link", () => {
         systemProgram: anchor.web3.SystemProgram.programId,
         arciumProgram: arciumProgramId,
       })
+      .remainingAccounts(shardAccounts())
       .rpc({ commitment: "confirmed" }));
 
     await awaitComputationFinalization(
@@ -956,7 +1441,7 @@ describe("blind-link", () => {
     );
 
     const session = await (program.account as any).psiSession.fetch(sessionPda);
-    expect(session.status).to.equal(2);
+    expect(session.status).to.deep.equal({ completed: {} });
 
     const decrypted = psiCipher.decrypt(
       session.resultCiphertext,
@@ -972,5 +1457,1172 @@ describe("blind-link", () => {
     expect(matchCount).to.equal(2);
 
     console.log("  Multi-match PSI: 2 matches found (alice, bob)");
+
+    // Result has been read — close the session and reclaim its rent
+    await withRetry(() => program.methods
+      .closeSession()
+      .accountsPartial({
+        user: provider.wallet.publicKey,
+        psiSession: sessionPda,
+      })
+      .rpc({ commitment: "confirmed" }));
+
+    const closed = await provider.connection.getAccountInfo(sessionPda);
+    expect(closed).to.equal(null);
+
+    console.log("  PSI session closed, rent reclaimed");
+  });
+
+  // ── Test: Batched Registration Queue ────────────────────────────
+
+  it("folds queued registrations into the registry via the crank", async () => {
+    const queuePda = registrationQueuePda();
+
+    if (!(await provider.connection.getAccountInfo(queuePda))) {
+      await withRetry(() => program.methods
+        .initializeRegistrationQueue()
+        .accountsPartial({
+          registrationQueue: queuePda,
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" }));
+    }
+
+    // Enqueue carol@queue.org — no MPC computation yet
+    carol = await fundedKeypair();
+    await depositStake(carol);
+
+    const clientPrivateKey = x25519.utils.randomSecretKey();
+    const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+    const sharedSecret = x25519.getSharedSecret(clientPrivateKey, mxePublicKey);
+    const cipher = new RescueCipher(sharedSecret);
+    const nonce = randomBytes(16);
+
+    const hashBuffer = await crypto.subtle.digest(
+      "SHA-256",
+      new TextEncoder().encode("carol@queue.org")
+    );
+    const hashArray = new Uint8Array(hashBuffer);
+    let contactHash = BigInt(0);
+    for (let i = 0; i < 16; i++) {
+      contactHash |= BigInt(hashArray[i]) << BigInt(i * 8);
+    }
+    const [encryptedHash, encryptedOwnerSecret] = cipher.encrypt(
+      [contactHash, deserializeLE(randomBytes(16))],
+      nonce
+    );
+
+    await withRetry(() => program.methods
+      .enqueueRegistration(
+        Array.from(Uint8Array.from(encryptedHash)) as number[],
+        Array.from(Uint8Array.from(encryptedOwnerSecret)) as number[],
+        Array.from(clientPublicKey) as number[],
+        new anchor.BN(deserializeLE(nonce).toString()),
+        null, // attested: no attestation key configured
+        new anchor.BN(0) // attestation_expiry: ignored as well
+      )
+      .accountsPartial({
+        user: carol.publicKey,
+        registrationQueue: queuePda,
+        registryState: registryPda,
+        registrationEscrow: escrowPda(carol.publicKey),
+        registration: registrationPda(carol.publicKey),
+      })
+      .signers([carol])
+      .rpc({ commitment: "confirmed" }));
+
+    let queue = await (program.account as any).registrationQueue.fetch(queuePda);
+    expect(queue.entries.length).to.be.greaterThanOrEqual(1);
+
+    await crankRegistrationQueue();
+
+    queue = await (program.account as any).registrationQueue.fetch(queuePda);
+    expect(queue.inFlight).to.equal(0);
+
+    console.log("  Registration queue drained, remaining:", queue.entries.length);
+  });
+
+  // ── Test: Deregistration and Stake Refund ───────────────────────
+
+  it("deregisters a registrant and refunds the stake", async () => {
+    const carolEscrow = escrowPda(carol.publicKey);
+    const carolRegistration = registrationPda(carol.publicKey);
+    const registration = await (program.account as any).registration.fetch(
+      carolRegistration
+    );
+    if (!registration.status.registered) {
+      console.log("  Carol's entry was not in the processed batch (skipped)");
+      return;
+    }
+
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await withRetry(() => program.methods
+      .deregisterUser(computationOffset)
+      .accountsPartial({
+        payer: carol.publicKey,
+        registryState: registryPda,
+        registration: carolRegistration,
+        signPdaAccount: signPda,
+        mxeAccount,
+        mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(
+          arciumEnv.arciumClusterOffset
+        ),
+        computationAccount: getComputationAccAddress(
+          arciumEnv.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          program.programId,
+          Buffer.from(getCompDefAccOffset("deregister_user")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+        poolAccount: feePool,
+        clockAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: arciumProgramId,
+      })
+      .remainingAccounts(shardAccounts())
+      .signers([carol])
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      provider,
+      computationOffset,
+      program.programId,
+      "confirmed"
+    );
+
+    // Deregistration closes the Registration, which unlocks the stake
+    expect(
+      await provider.connection.getAccountInfo(carolRegistration)
+    ).to.equal(null);
+
+    const before = await provider.connection.getBalance(carol.publicKey);
+    await withRetry(() => program.methods
+      .withdrawStake()
+      .accountsPartial({
+        user: carol.publicKey,
+        registrationEscrow: carolEscrow,
+        registration: carolRegistration,
+        escrowVault: null,
+        userTokenAccount: null,
+        tokenProgram: null,
+      })
+      .signers([carol])
+      .rpc({ commitment: "confirmed" }));
+
+    expect(await provider.connection.getAccountInfo(carolEscrow)).to.equal(null);
+    const after = await provider.connection.getBalance(carol.publicKey);
+    expect(after).to.be.greaterThan(before);
+
+    console.log("  Stake refunded:", after - before, "lamports");
+  });
+
+  it("only recovers registrations stranded by a lost callback", async () => {
+    // The provider's registration callback landed, so there is nothing to recover
+    let error: any;
+    try {
+      await program.methods
+        .recoverRegistration()
+        .accountsPartial({
+          user: provider.wallet.publicKey,
+          registryState: registryPda,
+          registration: registrationPda(provider.wallet.publicKey),
+        })
+        .rpc({ commitment: "confirmed" });
+    } catch (e: any) {
+      error = e;
+    }
+    expect(String(error)).to.include("RegistrationNotStranded");
+
+    const registration = await (program.account as any).registration.fetch(
+      registrationPda(provider.wallet.publicKey)
+    );
+    expect(registration.status).to.deep.equal({ registered: {} });
+
+    console.log("  Settled registration left untouched");
+  });
+
+  it("ignores a stale registration callback after recover and re-register", async () => {
+    const frank = await fundedKeypair();
+    await depositStake(frank);
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+
+    const register = async (computationOffset: anchor.BN) => {
+      const clientPrivateKey = x25519.utils.randomSecretKey();
+      const cipher = new RescueCipher(
+        x25519.getSharedSecret(clientPrivateKey, mxePublicKey)
+      );
+      const nonce = randomBytes(16);
+      const [encryptedHash, encryptedOwnerSecret] = cipher.encrypt(
+        [await hashContact("frank@example.com"), deserializeLE(randomBytes(16))],
+        nonce
+      );
+      await withRetry(() => program.methods
+        .registerUser(
+          computationOffset,
+          Array.from(Uint8Array.from(encryptedHash)) as number[],
+          Array.from(Uint8Array.from(encryptedOwnerSecret)) as number[],
+          Array.from(x25519.getPublicKey(clientPrivateKey)) as number[],
+          new anchor.BN(deserializeLE(nonce).toString()),
+          null, // attested: no attestation key configured
+          new anchor.BN(0) // attestation_expiry: ignored as well
+        )
+        .accountsPartial({
+          user: frank.publicKey,
+          registryState: registryPda,
+          registrationEscrow: escrowPda(frank.publicKey),
+          registration: registrationPda(frank.publicKey),
+          signPdaAccount: signPda,
+          mxeAccount,
+          mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+          executingPool: getExecutingPoolAccAddress(
+            arciumEnv.arciumClusterOffset
+          ),
+          computationAccount: getComputationAccAddress(
+            arciumEnv.arciumClusterOffset,
+            computationOffset
+          ),
+          compDefAccount: getCompDefAccAddress(
+            program.programId,
+            Buffer.from(getCompDefAccOffset("register_user")).readUInt32LE()
+          ),
+          clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+          poolAccount: feePool,
+          clockAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          arciumProgram: arciumProgramId,
+        })
+        .remainingAccounts(shardAccounts())
+        .signers([frank])
+        .rpc({ commitment: "confirmed" }));
+    };
+
+    const setSessionTimeout = (timeoutSecs: number) =>
+      withRetry(() => program.methods
+        .setSessionTimeout(new anchor.BN(timeoutSecs))
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" }));
+
+    const registry = await (program.account as any).registryState.fetch(registryPda);
+    const timeoutSecs = registry.sessionTimeoutSecs.toNumber();
+    await setSessionTimeout(1);
+
+    try {
+      // Strand the first registration: its computation is still running
+      // when the (1s) timeout passes and frank recovers it
+      const staleOffset = new anchor.BN(randomBytes(8), "hex");
+      await register(staleOffset);
+      await new Promise((r) => setTimeout(r, 2000));
+      await withRetry(() => program.methods
+        .recoverRegistration()
+        .accountsPartial({
+          user: frank.publicKey,
+          registryState: registryPda,
+          registration: registrationPda(frank.publicKey),
+        })
+        .signers([frank])
+        .rpc({ commitment: "confirmed" }));
+
+      // Re-register before the stranded computation's callback lands
+      const freshOffset = new anchor.BN(randomBytes(8), "hex");
+      await register(freshOffset);
+
+      // The late callback names the old computation, so it is refused
+      // instead of closing the new Pending registration
+      try {
+        await awaitComputationFinalization(
+          provider,
+          staleOffset,
+          program.programId,
+          "confirmed"
+        );
+      } catch (e: any) {
+        console.log("  Stale callback refused:", String(e).slice(0, 80));
+      }
+      await awaitComputationFinalization(
+        provider,
+        freshOffset,
+        program.programId,
+        "confirmed"
+      );
+
+      const registration = await (program.account as any).registration.fetch(
+        registrationPda(frank.publicKey)
+      );
+      expect(registration.status).to.deep.equal({ registered: {} });
+      expect(registration.computationOffset.toString()).to.equal(
+        freshOffset.toString()
+      );
+    } finally {
+      await setSessionTimeout(timeoutSecs);
+    }
+
+    console.log("  Re-registration survived the stale callback");
+  });
+
+  // ── Test: Atomic Identifier Update ──────────────────────────────
+
+  it("replaces a registrant's hash in one computation", async () => {
+    const erin = await fundedKeypair();
+    await depositStake(erin);
+
+    const clientPrivateKey = x25519.utils.randomSecretKey();
+    const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+    const sharedSecret = x25519.getSharedSecret(clientPrivateKey, mxePublicKey);
+    const cipher = new RescueCipher(sharedSecret);
+
+    const hashContact = async (contact: string) => {
+      const hashArray = new Uint8Array(
+        await crypto.subtle.digest("SHA-256", new TextEncoder().encode(contact))
+      );
+      let contactHash = BigInt(0);
+      for (let i = 0; i < 16; i++) {
+        contactHash |= BigInt(hashArray[i]) << BigInt(i * 8);
+      }
+      return contactHash;
+    };
+
+    const queueAccounts = (offset: anchor.BN, circuit: string) => ({
+      user: erin.publicKey,
+      registryState: registryPda,
+      registration: registrationPda(erin.publicKey),
+      signPdaAccount: signPda,
+      mxeAccount,
+      mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+      executingPool: getExecutingPoolAccAddress(arciumEnv.arciumClusterOffset),
+      computationAccount: getComputationAccAddress(
+        arciumEnv.arciumClusterOffset,
+        offset
+      ),
+      compDefAccount: getCompDefAccAddress(
+        program.programId,
+        Buffer.from(getCompDefAccOffset(circuit)).readUInt32LE()
+      ),
+      clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+      poolAccount: feePool,
+      clockAccount,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      arciumProgram: arciumProgramId,
+    });
+
+    // Register the old number
+    const oldNonce = randomBytes(16);
+    const erinOwnerSecret = deserializeLE(randomBytes(16));
+    const [oldHash, oldOwnerSecret] = cipher.encrypt(
+      [await hashContact("+15550100"), erinOwnerSecret],
+      oldNonce
+    );
+    const regOffset = new anchor.BN(randomBytes(8), "hex");
+    await withRetry(() => program.methods
+      .registerUser(
+        regOffset,
+        Array.from(Uint8Array.from(oldHash)) as number[],
+        Array.from(Uint8Array.from(oldOwnerSecret)) as number[],
+        Array.from(clientPublicKey) as number[],
+        new anchor.BN(deserializeLE(oldNonce).toString()),
+        null, // attested: no attestation key configured
+        new anchor.BN(0) // attestation_expiry: ignored as well
+      )
+      .accountsPartial({
+        ...queueAccounts(regOffset, "register_user"),
+        registrationEscrow: escrowPda(erin.publicKey),
+      })
+      .remainingAccounts(shardAccounts())
+      .signers([erin])
+      .rpc({ commitment: "confirmed" }));
+    await awaitComputationFinalization(
+      provider,
+      regOffset,
+      program.programId,
+      "confirmed"
+    );
+
+    const registered = await (program.account as any).registration.fetch(
+      registrationPda(erin.publicKey)
+    );
+
+    // Swap it for the new number
+    const newNonce = randomBytes(16);
+    const [newHash, newOwnerSecret] = cipher.encrypt(
+      [await hashContact("+15550199"), erinOwnerSecret],
+      newNonce
+    );
+    const updateOffset = new anchor.BN(randomBytes(8), "hex");
+    await withRetry(() => program.methods
+      .updateRegistration(
+        updateOffset,
+        Array.from(Uint8Array.from(newHash)) as number[],
+        Array.from(Uint8Array.from(newOwnerSecret)) as number[],
+        Array.from(clientPublicKey) as number[],
+        new anchor.BN(deserializeLE(newNonce).toString()),
+        null, // attested: no attestation key configured
+        new anchor.BN(0) // attestation_expiry: ignored as well
+      )
+      .accountsPartial(queueAccounts(updateOffset, "update_registration"))
+      .remainingAccounts(shardAccounts())
+      .signers([erin])
+      .rpc({ commitment: "confirmed" }));
+    await awaitComputationFinalization(
+      provider,
+      updateOffset,
+      program.programId,
+      "confirmed"
+    );
+
+    const registration = await (program.account as any).registration.fetch(
+      registrationPda(erin.publicKey)
+    );
+    expect(registration.status).to.deep.equal({ registered: {} });
+    // Only an applied update stores a fresh receipt
+    expect(registration.receiptNonce.eq(registered.receiptNonce)).to.equal(false);
+
+    // The old number no longer matches; the new one does
+    const [oldMatched, newMatched] = await runIntersection([
+      "+15550100",
+      "+15550199",
+    ]);
+    expect(oldMatched).to.equal(BigInt(0));
+    expect(newMatched).to.equal(BigInt(1));
+
+    console.log("  Registration updated at offset", updateOffset.toString());
+  });
+
+  // ── Test: Private Membership Check ──────────────────────────────
+
+  it("privately confirms a registration landed", async () => {
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+
+    // Check `contact` against the provider's own registration receipt
+    const checkMembership = async (contact: string): Promise<bigint> => {
+      const clientPrivateKey = x25519.utils.randomSecretKey();
+      const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
+      const sharedSecret = x25519.getSharedSecret(clientPrivateKey, mxePublicKey);
+      const cipher = new RescueCipher(sharedSecret);
+      const nonce = randomBytes(16);
+
+      const hashArray = new Uint8Array(
+        await crypto.subtle.digest("SHA-256", new TextEncoder().encode(contact))
+      );
+      let contactHash = BigInt(0);
+      for (let i = 0; i < 16; i++) {
+        contactHash |= BigInt(hashArray[i]) << BigInt(i * 8);
+      }
+      const [encryptedHash] = cipher.encrypt([contactHash], nonce);
+
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      const [membershipPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("membership_session"),
+          provider.wallet.publicKey.toBuffer(),
+          computationOffset.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      await withRetry(() => program.methods
+        .checkMembership(
+          computationOffset,
+          Array.from(Uint8Array.from(encryptedHash)) as number[],
+          Array.from(clientPublicKey) as number[],
+          new anchor.BN(deserializeLE(nonce).toString())
+        )
+        .accountsPartial({
+          user: provider.wallet.publicKey,
+          membershipSession: membershipPda,
+          registryState: registryPda,
+          registration: registrationPda(provider.wallet.publicKey),
+          signPdaAccount: signPda,
+          mxeAccount,
+          mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+          executingPool: getExecutingPoolAccAddress(
+            arciumEnv.arciumClusterOffset
+          ),
+          computationAccount: getComputationAccAddress(
+            arciumEnv.arciumClusterOffset,
+            computationOffset
+          ),
+          compDefAccount: getCompDefAccAddress(
+            program.programId,
+            Buffer.from(getCompDefAccOffset("check_membership")).readUInt32LE()
+          ),
+          clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+          poolAccount: feePool,
+          clockAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          arciumProgram: arciumProgramId,
+        })
+        .remainingAccounts(shardAccounts())
+        .rpc({ commitment: "confirmed" }));
+
+      await awaitComputationFinalization(
+        provider,
+        computationOffset,
+        program.programId,
+        "confirmed"
+      );
+
+      const session = await (program.account as any).membershipSession.fetch(
+        membershipPda
+      );
+      expect(session.status).to.deep.equal({ completed: {} });
+      const [isMember] = cipher.decrypt(
+        [session.resultCiphertext],
+        Uint8Array.from(session.resultNonce)
+      );
+
+      await withRetry(() => program.methods
+        .closeMembershipSession()
+        .accountsPartial({
+          user: provider.wallet.publicKey,
+          membershipSession: membershipPda,
+        })
+        .rpc({ commitment: "confirmed" }));
+      return isMember;
+    };
+
+    // alice@example.com is the provider's own registration
+    expect(await checkMembership("alice@example.com")).to.equal(BigInt(1));
+    // Another registrant's hash is present, but not the caller's to check
+    expect(await checkMembership("+15550199")).to.equal(BigInt(0));
+
+    console.log("  Membership confirmed for alice@example.com");
+  });
+
+  // ── Test: Hit Count ─────────────────────────────────────────────
+
+  it("tells a registrant how often they were discovered", async () => {
+    // Read alice's hit count, encrypted to a fresh key
+    const readHitCount = async (): Promise<bigint> => {
+      const clientPrivateKey = x25519.utils.randomSecretKey();
+      const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
+      const mxePublicKey = await fetchMXEKey(provider, program.programId);
+      const sharedSecret = x25519.getSharedSecret(clientPrivateKey, mxePublicKey);
+      const cipher = new RescueCipher(sharedSecret);
+      const nonce = randomBytes(16);
+
+      const hashArray = new Uint8Array(
+        await crypto.subtle.digest(
+          "SHA-256",
+          new TextEncoder().encode("alice@example.com")
+        )
+      );
+      let contactHash = BigInt(0);
+      for (let i = 0; i < 16; i++) {
+        contactHash |= BigInt(hashArray[i]) << BigInt(i * 8);
+      }
+      const [encryptedHash, encryptedOwnerSecret] = cipher.encrypt(
+        [contactHash, aliceOwnerSecret],
+        nonce
+      );
+
+      const resultPromise = new Promise<any>((resolve) => {
+        const listener = program.addEventListener("hitCountEvent", (event: any) => {
+          program.removeEventListener(listener as number);
+          resolve(event);
+        });
+      });
+
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      await withRetry(() => program.methods
+        .readMyHitCount(
+          computationOffset,
+          Array.from(Uint8Array.from(encryptedHash)) as number[],
+          Array.from(Uint8Array.from(encryptedOwnerSecret)) as number[],
+          Array.from(clientPublicKey) as number[],
+          new anchor.BN(deserializeLE(nonce).toString())
+        )
+        .accountsPartial({
+          user: provider.wallet.publicKey,
+          registration: registrationPda(provider.wallet.publicKey),
+          registryState: registryPda,
+          signPdaAccount: signPda,
+          mxeAccount,
+          mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+          executingPool: getExecutingPoolAccAddress(
+            arciumEnv.arciumClusterOffset
+          ),
+          computationAccount: getComputationAccAddress(
+            arciumEnv.arciumClusterOffset,
+            computationOffset
+          ),
+          compDefAccount: getCompDefAccAddress(
+            program.programId,
+            Buffer.from(getCompDefAccOffset("read_my_hit_count")).readUInt32LE()
+          ),
+          clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+          poolAccount: feePool,
+          clockAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          arciumProgram: arciumProgramId,
+        })
+        .remainingAccounts(shardAccounts())
+        .rpc({ commitment: "confirmed" }));
+
+      await awaitComputationFinalization(
+        provider,
+        computationOffset,
+        program.programId,
+        "confirmed"
+      );
+
+      const event = await resultPromise;
+      expect(event.user.toString()).to.equal(provider.wallet.publicKey.toString());
+      const [hits] = cipher.decrypt(
+        [event.resultCiphertext],
+        Uint8Array.from(event.resultNonce)
+      );
+      return hits;
+    };
+
+    // One more query matching alice adds exactly one hit
+    const before = await readHitCount();
+    await runIntersection(["alice@example.com", "nobody@example.com"]);
+    const hits = await readHitCount();
+    expect(hits).to.equal(before + BigInt(1));
+
+    console.log("  alice@example.com discovered", hits.toString(), "times");
+  });
+
+  // ── Test: Per-Entry Match Rate Limit ────────────────────────────
+
+  it("stops reporting an entry once it hits its epoch match cap", async () => {
+    const DAY = 24 * 60 * 60;
+    const setMatchRateLimit = (cap: number) =>
+      withRetry(() => program.methods
+        .setMatchRateLimit(new anchor.BN(cap), new anchor.BN(DAY))
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" }));
+
+    // alice has already matched this epoch, so a cap of 1 hides her
+    await setMatchRateLimit(1);
+    try {
+      const capped = await runIntersection(["alice@example.com"]);
+      expect(capped[0]).to.equal(BigInt(0));
+    } finally {
+      await setMatchRateLimit(0);
+    }
+
+    const uncapped = await runIntersection(["alice@example.com"]);
+    expect(uncapped[0]).to.not.equal(BigInt(0));
+
+    console.log("  Capped lookups of alice@example.com read as non-matches");
+  });
+
+  // ── Test: Per-Query Match Cap ───────────────────────────────────
+
+  it("suppresses queries over the match cap and counts them", async () => {
+    const setQueryMatchCap = (cap: number) =>
+      withRetry(() => program.methods
+        .setQueryMatchCap(new anchor.BN(cap))
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" }));
+
+    // alice and bob are both registered: two matches exceed a cap of one
+    await setQueryMatchCap(1);
+    try {
+      const suppressed = await runIntersection([
+        "alice@example.com",
+        "bob@unknown.com",
+      ]);
+      expect(suppressed.every((v) => v === BigInt(0))).to.equal(true);
+    } finally {
+      await setQueryMatchCap(0);
+    }
+
+    // Only the authority can decrypt the anomaly count
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+    const authorityPrivateKey = x25519.utils.randomSecretKey();
+    const authorityCipher = new RescueCipher(
+      x25519.getSharedSecret(authorityPrivateKey, mxePublicKey)
+    );
+    const revealNonce = randomBytes(16);
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const countPromise = new Promise<any>((resolve) => {
+      const listener = program.addEventListener(
+        "anomalyCountEvent",
+        (event: any) => {
+          program.removeEventListener(listener as number);
+          resolve(event);
+        }
+      );
+    });
+
+    await withRetry(() => program.methods
+      .revealAnomalyCount(
+        computationOffset,
+        Array.from(x25519.getPublicKey(authorityPrivateKey)) as number[],
+        new anchor.BN(deserializeLE(revealNonce).toString())
+      )
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        registryState: registryPda,
+        signPdaAccount: signPda,
+        mxeAccount,
+        mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+        executingPool: getExecutingPoolAccAddress(
+          arciumEnv.arciumClusterOffset
+        ),
+        computationAccount: getComputationAccAddress(
+          arciumEnv.arciumClusterOffset,
+          computationOffset
+        ),
+        compDefAccount: getCompDefAccAddress(
+          program.programId,
+          Buffer.from(getCompDefAccOffset("reveal_anomaly_count")).readUInt32LE()
+        ),
+        clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+        poolAccount: feePool,
+        clockAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        arciumProgram: arciumProgramId,
+      })
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      provider,
+      computationOffset,
+      program.programId,
+      "confirmed"
+    );
+
+    const event = await countPromise;
+    const [anomalousQueries] = authorityCipher.decrypt(
+      [event.resultCiphertext],
+      Uint8Array.from(event.resultNonce)
+    );
+    expect(anomalousQueries >= BigInt(1)).to.equal(true);
+
+    console.log("  Anomalous queries:", anomalousQueries.toString());
+  });
+
+  // ── Test: Canaries ──────────────────────────────────────────────
+
+  it("counts intersections that match a canary entry", async () => {
+    const canary = "canary@blind-link.test";
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+    const queueAccounts = (circuit: string, computationOffset: anchor.BN) => ({
+      authority: provider.wallet.publicKey,
+      registryState: registryPda,
+      signPdaAccount: signPda,
+      mxeAccount,
+      mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+      executingPool: getExecutingPoolAccAddress(arciumEnv.arciumClusterOffset),
+      computationAccount: getComputationAccAddress(
+        arciumEnv.arciumClusterOffset,
+        computationOffset
+      ),
+      compDefAccount: getCompDefAccAddress(
+        program.programId,
+        Buffer.from(getCompDefAccOffset(circuit)).readUInt32LE()
+      ),
+      clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+      poolAccount: feePool,
+      clockAccount,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      arciumProgram: arciumProgramId,
+    });
+
+    // Insert the canary with a random ownership secret nobody keeps
+    const insertPrivateKey = x25519.utils.randomSecretKey();
+    const insertCipher = new RescueCipher(
+      x25519.getSharedSecret(insertPrivateKey, mxePublicKey)
+    );
+    const insertNonce = randomBytes(16);
+    const hashArray = new Uint8Array(
+      await crypto.subtle.digest("SHA-256", new TextEncoder().encode(canary))
+    );
+    let canaryHash = BigInt(0);
+    for (let i = 0; i < 16; i++) {
+      canaryHash |= BigInt(hashArray[i]) << BigInt(i * 8);
+    }
+    const [encryptedHash, encryptedOwnerSecret] = insertCipher.encrypt(
+      [canaryHash, deserializeLE(randomBytes(16))],
+      insertNonce
+    );
+
+    const insertOffset = new anchor.BN(randomBytes(8), "hex");
+    const insertedPromise = new Promise<any>((resolve) => {
+      const listener = program.addEventListener(
+        "canaryInsertedEvent",
+        (event: any) => {
+          program.removeEventListener(listener as number);
+          resolve(event);
+        }
+      );
+    });
+
+    await withRetry(() => program.methods
+      .insertCanary(
+        insertOffset,
+        Array.from(Uint8Array.from(encryptedHash)),
+        Array.from(Uint8Array.from(encryptedOwnerSecret)),
+        Array.from(x25519.getPublicKey(insertPrivateKey)) as number[],
+        new anchor.BN(deserializeLE(insertNonce).toString())
+      )
+      .accountsPartial(queueAccounts("insert_canary", insertOffset))
+      .remainingAccounts(shardAccounts())
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      provider,
+      insertOffset,
+      program.programId,
+      "confirmed"
+    );
+    const insertedEvent = await insertedPromise;
+    const [inserted] = insertCipher.decrypt(
+      [insertedEvent.insertedCiphertext],
+      Uint8Array.from(insertedEvent.insertedNonce)
+    );
+    expect(inserted).to.equal(BigInt(1));
+
+    // A canary matches like any registered contact
+    const result = await runIntersection([canary]);
+    expect(result[0]).to.not.equal(BigInt(0));
+
+    // Only the authority can decrypt the canary hit count
+    const authorityPrivateKey = x25519.utils.randomSecretKey();
+    const authorityCipher = new RescueCipher(
+      x25519.getSharedSecret(authorityPrivateKey, mxePublicKey)
+    );
+    const revealNonce = randomBytes(16);
+    const revealOffset = new anchor.BN(randomBytes(8), "hex");
+    const hitsPromise = new Promise<any>((resolve) => {
+      const listener = program.addEventListener(
+        "canaryHitsEvent",
+        (event: any) => {
+          program.removeEventListener(listener as number);
+          resolve(event);
+        }
+      );
+    });
+
+    await withRetry(() => program.methods
+      .revealCanaryHits(
+        revealOffset,
+        Array.from(x25519.getPublicKey(authorityPrivateKey)) as number[],
+        new anchor.BN(deserializeLE(revealNonce).toString())
+      )
+      .accountsPartial(queueAccounts("reveal_canary_hits", revealOffset))
+      .rpc({ commitment: "confirmed" }));
+
+    await awaitComputationFinalization(
+      provider,
+      revealOffset,
+      program.programId,
+      "confirmed"
+    );
+
+    const event = await hitsPromise;
+    const [hits] = authorityCipher.decrypt(
+      [event.resultCiphertext],
+      Uint8Array.from(event.resultNonce)
+    );
+    expect(hits >= BigInt(1)).to.equal(true);
+
+    console.log("  Canary hits:", hits.toString());
+  });
+
+  // ── Test: Pause / Unpause Discovery ─────────────────────────────
+
+  it("pauses and unpauses discovery", async () => {
+    const PAUSE_DISCOVERY = 1 << 1;
+
+    await withRetry(() => program.methods
+      .pauseRegistry(PAUSE_DISCOVERY)
+      .accountsPartial({
+        registryState: registryPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" }));
+
+    let registry = await (program.account as any).registryState.fetch(registryPda);
+    expect(registry.paused & PAUSE_DISCOVERY).to.equal(PAUSE_DISCOVERY);
+
+    let error: any;
+    try {
+      await runIntersection(["alice@example.com"]);
+    } catch (e: any) {
+      error = e;
+    }
+    expect(String(error)).to.include("RegistryPaused");
+
+    await withRetry(() => program.methods
+      .unpauseRegistry(PAUSE_DISCOVERY)
+      .accountsPartial({
+        registryState: registryPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" }));
+
+    registry = await (program.account as any).registryState.fetch(registryPda);
+    expect(registry.paused & PAUSE_DISCOVERY).to.equal(0);
+
+    console.log("  Discovery pause toggled");
+  });
+
+  // ── Test: Attested Registration ─────────────────────────────────
+
+  it("rejects unattested registrations while an attestation key is set", async () => {
+    const attester = anchor.web3.Keypair.generate();
+    const dave = await fundedKeypair();
+    await depositStake(dave);
+
+    await withRetry(() => program.methods
+      .setAttestationKey(attester.publicKey)
+      .accountsPartial({
+        registryState: registryPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" }));
+
+    try {
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      let error: any;
+      try {
+        await program.methods
+          .registerUser(
+            computationOffset,
+            Array.from(randomBytes(32)) as number[],
+            Array.from(randomBytes(32)) as number[],
+            Array.from(randomBytes(32)) as number[],
+            new anchor.BN(1),
+            null,
+            new anchor.BN(Math.floor(Date.now() / 1000) + 600)
+          )
+          .accountsPartial({
+            user: dave.publicKey,
+            registryState: registryPda,
+            registrationEscrow: escrowPda(dave.publicKey),
+            registration: registrationPda(dave.publicKey),
+            signPdaAccount: signPda,
+            mxeAccount,
+            mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+            executingPool: getExecutingPoolAccAddress(
+              arciumEnv.arciumClusterOffset
+            ),
+            computationAccount: getComputationAccAddress(
+              arciumEnv.arciumClusterOffset,
+              computationOffset
+            ),
+            compDefAccount: getCompDefAccAddress(
+              program.programId,
+              Buffer.from(getCompDefAccOffset("register_user")).readUInt32LE()
+            ),
+            clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+            poolAccount: feePool,
+            clockAccount,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            arciumProgram: arciumProgramId,
+          })
+          .remainingAccounts(shardAccounts())
+          .signers([dave])
+          .rpc({ commitment: "confirmed" });
+      } catch (e: any) {
+        error = e;
+      }
+      expect(String(error)).to.include("AttestationMissing");
+    } finally {
+      await withRetry(() => program.methods
+        .setAttestationKey(null)
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" }));
+    }
+
+    console.log("  Unattested registration rejected");
+  });
+
+  it("rejects a registration whose hash differs from the attested one", async () => {
+    const attester = anchor.web3.Keypair.generate();
+    const dave = await fundedKeypair();
+    await depositStake(dave);
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+
+    // The attester verified and signs dave's real identifier...
+    const { attested, expiry, ed25519Ix } = await signAttestation(
+      attester,
+      dave.publicKey,
+      "dave@example.com"
+    );
+
+    // ...but dave submits someone else's identifier
+    const clientPrivateKey = x25519.utils.randomSecretKey();
+    const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
+    const cipher = new RescueCipher(
+      x25519.getSharedSecret(clientPrivateKey, mxePublicKey)
+    );
+    const nonce = randomBytes(16);
+    const [encryptedHash, encryptedOwnerSecret] = cipher.encrypt(
+      [await hashContact("victim@example.com"), deserializeLE(randomBytes(16))],
+      nonce
+    );
+
+    await withRetry(() => program.methods
+      .setAttestationKey(attester.publicKey)
+      .accountsPartial({
+        registryState: registryPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" }));
+
+    try {
+      const computationOffset = new anchor.BN(randomBytes(8), "hex");
+      await withRetry(() => program.methods
+        .registerUser(
+          computationOffset,
+          Array.from(Uint8Array.from(encryptedHash)) as number[],
+          Array.from(Uint8Array.from(encryptedOwnerSecret)) as number[],
+          Array.from(clientPublicKey) as number[],
+          new anchor.BN(deserializeLE(nonce).toString()),
+          attested,
+          expiry
+        )
+        .accountsPartial({
+          user: dave.publicKey,
+          registryState: registryPda,
+          registrationEscrow: escrowPda(dave.publicKey),
+          registration: registrationPda(dave.publicKey),
+          signPdaAccount: signPda,
+          mxeAccount,
+          mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+          executingPool: getExecutingPoolAccAddress(
+            arciumEnv.arciumClusterOffset
+          ),
+          computationAccount: getComputationAccAddress(
+            arciumEnv.arciumClusterOffset,
+            computationOffset
+          ),
+          compDefAccount: getCompDefAccAddress(
+            program.programId,
+            Buffer.from(getCompDefAccOffset("register_user")).readUInt32LE()
+          ),
+          clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+          poolAccount: feePool,
+          clockAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          arciumProgram: arciumProgramId,
+        })
+        .remainingAccounts(shardAccounts())
+        .preInstructions([ed25519Ix])
+        .signers([dave])
+        .rpc({ commitment: "confirmed" }));
+      await awaitComputationFinalization(
+        provider,
+        computationOffset,
+        program.programId,
+        "confirmed"
+      );
+
+      // The signature verified, but the circuit refused the insert and the
+      // callback closed the Registration
+      const registration = await (program.account as any).registration.fetchNullable(
+        registrationPda(dave.publicKey)
+      );
+      expect(registration).to.equal(null);
+    } finally {
+      await withRetry(() => program.methods
+        .setAttestationKey(null)
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" }));
+    }
+
+    console.log("  Mismatched registration ciphertext rejected");
+  });
+
+  it("closes a queued registration whose hash differs from the attested one", async () => {
+    const attester = anchor.web3.Keypair.generate();
+    const erin = await fundedKeypair();
+    await depositStake(erin);
+    const mxePublicKey = await fetchMXEKey(provider, program.programId);
+
+    const { attested, expiry, ed25519Ix } = await signAttestation(
+      attester,
+      erin.publicKey,
+      "erin@example.com"
+    );
+
+    const clientPrivateKey = x25519.utils.randomSecretKey();
+    const clientPublicKey = x25519.getPublicKey(clientPrivateKey);
+    const cipher = new RescueCipher(
+      x25519.getSharedSecret(clientPrivateKey, mxePublicKey)
+    );
+    const nonce = randomBytes(16);
+    const [encryptedHash, encryptedOwnerSecret] = cipher.encrypt(
+      [await hashContact("victim@example.com"), deserializeLE(randomBytes(16))],
+      nonce
+    );
+
+    await withRetry(() => program.methods
+      .setAttestationKey(attester.publicKey)
+      .accountsPartial({
+        registryState: registryPda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" }));
+
+    try {
+      await withRetry(() => program.methods
+        .enqueueRegistration(
+          Array.from(Uint8Array.from(encryptedHash)) as number[],
+          Array.from(Uint8Array.from(encryptedOwnerSecret)) as number[],
+          Array.from(clientPublicKey) as number[],
+          new anchor.BN(deserializeLE(nonce).toString()),
+          attested,
+          expiry
+        )
+        .accountsPartial({
+          user: erin.publicKey,
+          registrationQueue: registrationQueuePda(),
+          registryState: registryPda,
+          registrationEscrow: escrowPda(erin.publicKey),
+          registration: registrationPda(erin.publicKey),
+        })
+        .preInstructions([ed25519Ix])
+        .signers([erin])
+        .rpc({ commitment: "confirmed" }));
+
+      const balanceBefore = await provider.connection.getBalance(erin.publicKey);
+      await crankRegistrationQueue();
+
+      // Nothing was inserted, so no receipt: the Registration is closed and
+      // its rent returned to erin
+      const registration = await (program.account as any).registration.fetchNullable(
+        registrationPda(erin.publicKey)
+      );
+      expect(registration).to.equal(null);
+      const balanceAfter = await provider.connection.getBalance(erin.publicKey);
+      expect(balanceAfter).to.be.greaterThan(balanceBefore);
+    } finally {
+      await withRetry(() => program.methods
+        .setAttestationKey(null)
+        .accountsPartial({
+          registryState: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" }));
+    }
+
+    console.log("  Mismatched queued registration closed");
   });
 });